@@ -5,7 +5,7 @@ import test from 'ava'
 import jimp from 'jimp-compact'
 import fetch from 'node-fetch'
 
-import { Resvg, renderAsync } from '../index'
+import { FontDatabase, Resvg, ResvgError, ResvgRenderOptions, listFonts, renderAsync, setLogger } from '../index'
 
 import { jimpToRgbaPixels } from './helper'
 
@@ -610,6 +610,739 @@ test('should render using font buffer provided by options', async (t) => {
   t.is(jimp.diff(expectedResult, actualPng, 0.01).percent, 0) // 0 means similar, 1 means not similar
 })
 
+test('should encode WebP', (t) => {
+  const svg = `<svg width="10px" height="5px" viewBox="0 0 10 5" version="1.1" xmlns="http://www.w3.org/2000/svg">
+    <rect fill="red" x="0" y="0" width="5" height="5"></rect>
+    <rect fill="green" x="5" y="0" width="5" height="5"></rect>
+  </svg>`
+  const resvg = new Resvg(svg)
+  const image = resvg.render()
+  const lossy = image.asWebp({ quality: 50 })
+  const lossless = image.asWebp({ lossless: true })
+
+  t.is(lossy.toString('ascii', 0, 4), 'RIFF')
+  t.is(lossy.toString('ascii', 8, 12), 'WEBP')
+  t.is(lossy.toString('ascii', 12, 16), 'VP8 ')
+  t.is(lossless.toString('ascii', 12, 16), 'VP8L')
+
+  // The alpha of a lossy image is stored in an ALPH chunk of the extended format.
+  const halfTransparent = '<svg width="10" height="5" xmlns="http://www.w3.org/2000/svg"><rect width="5" height="5"/></svg>'
+  const alpha = new Resvg(halfTransparent).render().asWebp()
+  t.is(alpha.toString('ascii', 12, 16), 'VP8X')
+  t.is(alpha.toString('ascii', 30, 34), 'ALPH')
+  t.is(alpha.readUInt32LE(4), alpha.length - 8)
+})
+
+test('should encode JPEG and flatten onto the background', async (t) => {
+  const svg = `<svg width="10px" height="10px" viewBox="0 0 10 10" version="1.1" xmlns="http://www.w3.org/2000/svg">
+    <rect fill="red" x="0" y="0" width="5" height="10"></rect>
+  </svg>`
+  const resvg = new Resvg(svg, { background: '#0000ff' })
+  const jpegBuffer = resvg.render().asJpeg({ quality: 100, chromaSubsampling: '4:4:4' })
+  const result = await jimp.read(jpegBuffer)
+
+  t.is(jpegBuffer[0], 0xff)
+  t.is(jpegBuffer[1], 0xd8)
+  t.is(result.getWidth(), 10)
+  const left = jimp.intToRGBA(result.getPixelColor(1, 5))
+  const right = jimp.intToRGBA(result.getPixelColor(8, 5))
+  t.true(left.r > 240 && left.b < 16)
+  t.true(right.b > 240 && right.r < 16)
+})
+
+test('should encode AVIF', (t) => {
+  const svg = `<svg width="16px" height="16px" viewBox="0 0 16 16" version="1.1" xmlns="http://www.w3.org/2000/svg">
+    <circle fill="red" cx="8" cy="8" r="6"></circle>
+  </svg>`
+  const resvg = new Resvg(svg)
+  const avif = resvg.render().asAvif({ quality: 60, speed: 10 })
+
+  t.is(avif.toString('ascii', 4, 8), 'ftyp')
+  t.is(avif.toString('ascii', 8, 12), 'avif')
+})
+
+test('should export a vector PDF sized to the SVG', async (t) => {
+  const filePath = '../example/bbox.svg'
+  const svg = await fs.readFile(join(__dirname, filePath))
+  const resvg = new Resvg(svg, {
+    font: {
+      loadSystemFonts: false,
+    },
+  })
+  const pdf = resvg.toPdf().toString('latin1')
+
+  t.true(pdf.startsWith('%PDF-'))
+  t.true(pdf.trimEnd().endsWith('%%EOF'))
+  t.true(pdf.includes(`/MediaBox [0 0 ${resvg.width} ${resvg.height}]`))
+})
+
+test('should export the reflect and repeat gradients to PDF', (t) => {
+  const gradient = (spreadMethod: string) => `<svg width="100" height="100" xmlns="http://www.w3.org/2000/svg">
+  <linearGradient id="g" x2="0.25" spreadMethod="${spreadMethod}">
+    <stop offset="0" stop-color="red"/><stop offset="1" stop-color="blue"/>
+  </linearGradient>
+  <rect width="100" height="100" fill="url(#g)"/>
+</svg>`
+  const toPdf = (spreadMethod: string) => new Resvg(gradient(spreadMethod)).toPdf().toString('latin1')
+
+  // The gradient covers a quarter of the rect, it is repeated 4 times, every other one mirrored when reflected.
+  t.true(toPdf('reflect').includes('/Encode [0 1 1 0 0 1 1 0]'))
+  t.true(toPdf('repeat').includes('/Encode [0 1 0 1 0 1 0 1]'))
+  t.false(toPdf('pad').includes('/Encode [0 1 0 1'))
+})
+
+test('should write PNG metadata chunks', (t) => {
+  const svg = `<svg width="16px" height="16px" viewBox="0 0 16 16" version="1.1" xmlns="http://www.w3.org/2000/svg">
+    <circle fill="red" cx="8" cy="8" r="6"></circle>
+  </svg>`
+  const resvg = new Resvg(svg, { dpi: 144 })
+  const png = resvg.render().asPng({ writeDpi: true, text: { Title: 'resvg' }, srgb: true })
+
+  const phys = png.indexOf('pHYs')
+  t.true(phys > 0)
+  // 144 DPI is 5669 pixels per meter
+  t.is(png.readUInt32BE(phys + 4), 5669)
+  t.is(png.readUInt32BE(phys + 8), 5669)
+  t.is(png[phys + 12], 1)
+  t.true(png.includes('tEXtTitle\0resvg'))
+  t.true(png.includes('sRGB'))
+
+  const plain = resvg.render().asPng()
+  t.is(plain.indexOf('pHYs'), -1)
+  t.is(plain.indexOf('tEXt'), -1)
+})
+
+test('should encode PNG with compression and filter options', async (t) => {
+  const filePath = '../example/text.svg'
+  const svg = await fs.readFile(join(__dirname, filePath))
+  const resvg = new Resvg(svg, {
+    font: {
+      fontFiles: ['./example/SourceHanSerifCN-Light-subset.ttf'],
+      loadSystemFonts: false,
+      defaultFontFamily: 'Source Han Serif CN Light',
+    },
+  })
+  const pngData = resvg.render()
+  const fast = pngData.asPng({ compression: 'fast', filter: 'none' })
+  const max = pngData.asPng({ compression: 'max', filter: 'adaptive' })
+
+  t.true(max.length < fast.length)
+  const fastImage = await jimp.read(fast)
+  const maxImage = await jimp.read(max)
+  t.is(maxImage.getWidth(), fastImage.getWidth())
+  t.is(maxImage.getHeight(), fastImage.getHeight())
+  t.true(maxImage.bitmap.data.equals(fastImage.bitmap.data))
+})
+
+test('should encode palette PNG', async (t) => {
+  const svg = `<svg width="32px" height="32px" viewBox="0 0 32 32" version="1.1" xmlns="http://www.w3.org/2000/svg">
+    <rect fill="#00f" x="0" y="0" width="16" height="32"></rect>
+    <rect fill="red" fill-opacity="0.5" x="16" y="0" width="16" height="16"></rect>
+  </svg>`
+  const resvg = new Resvg(svg, { shapeRendering: 1 })
+  const pngData = resvg.render()
+  const rgba = pngData.asPng()
+  const indexed = pngData.asPng({ palette: true })
+
+  // Color type 3 is indexed
+  t.is(indexed[25], 3)
+  t.true(indexed.includes('PLTE'))
+  t.true(indexed.includes('tRNS'))
+  t.true(indexed.length < rgba.length)
+
+  // Few colors are kept exactly
+  const rgbaImage = await jimp.read(rgba)
+  const indexedImage = await jimp.read(indexed)
+  t.true(indexedImage.bitmap.data.equals(rgbaImage.bitmap.data))
+
+  const reduced = pngData.asPng({ palette: true, maxColors: 2, dither: true })
+  t.is(reduced[25], 3)
+  t.is(reduced.readUInt32BE(reduced.indexOf('PLTE') - 4), 2 * 3)
+})
+
+test('should write the sRGB chunks of palette PNG before PLTE', (t) => {
+  const svg = `<svg width="4px" height="4px" viewBox="0 0 4 4" version="1.1" xmlns="http://www.w3.org/2000/svg">
+    <rect fill="red" fill-opacity="0.5" x="0" y="0" width="2" height="4"></rect>
+  </svg>`
+  const png = new Resvg(svg).render().asPng({ palette: true, srgb: true, writeDpi: true, text: { Title: 'resvg' } })
+
+  const chunks: string[] = []
+  for (let offset = 8; offset < png.length; offset += png.readUInt32BE(offset) + 12) {
+    chunks.push(png.toString('ascii', offset + 4, offset + 8))
+  }
+  t.deepEqual(chunks, ['IHDR', 'sRGB', 'gAMA', 'cHRM', 'tEXt', 'PLTE', 'tRNS', 'pHYs', 'IDAT', 'IEND'])
+})
+
+test('should get unpremultiplied pixels in other channel orders', (t) => {
+  const svg = `<svg width="2px" height="2px" viewBox="0 0 2 2" version="1.1" xmlns="http://www.w3.org/2000/svg">
+    <rect fill="rgb(200, 100, 50)" fill-opacity="0.5" x="0" y="0" width="2" height="2"></rect>
+  </svg>`
+  const resvg = new Resvg(svg)
+  const pngData = resvg.render()
+
+  t.true(pngData.getPixels().equals(pngData.pixels))
+
+  const straight = pngData.getPixels({ premultiplied: false })
+  t.is(straight.length, 2 * 2 * 4)
+  t.true(Math.abs(straight[0] - 200) <= 1)
+  t.true(Math.abs(straight[1] - 100) <= 1)
+  t.is(straight[3], 128)
+
+  const bgra = pngData.getPixels({ premultiplied: false, format: 'bgra' })
+  t.deepEqual([...bgra.subarray(0, 4)], [straight[2], straight[1], straight[0], straight[3]])
+  const argb = pngData.getPixels({ format: 'argb' })
+  t.deepEqual([...argb.subarray(0, 4)], [pngData.pixels[3], pngData.pixels[0], pngData.pixels[1], pngData.pixels[2]])
+  t.is(pngData.getPixels({ format: 'rgb' }).length, 2 * 2 * 3)
+  t.is(pngData.getPixels({ format: 'gray' }).length, 2 * 2)
+})
+
+test('should render into an existing buffer', (t) => {
+  const svg = `<svg width="4px" height="2px" viewBox="0 0 4 2" version="1.1" xmlns="http://www.w3.org/2000/svg">
+    <rect fill="#00f" x="0" y="0" width="4" height="2"></rect>
+  </svg>`
+  const resvg = new Resvg(svg)
+  const expected = resvg.render().pixels
+
+  const frame = Buffer.alloc(4 * 4 * 8)
+  resvg.renderInto(frame.subarray(0, expected.length))
+  t.true(frame.subarray(0, expected.length).equals(expected))
+
+  // A 8x4 frame, the render is placed at (3, 1)
+  frame.fill(0)
+  resvg.renderInto(frame, { stride: 8 * 4, x: 3, y: 1 })
+  t.deepEqual([...frame.subarray((1 * 8 + 3) * 4, (1 * 8 + 4) * 4)], [0, 0, 255, 255])
+  t.deepEqual([...frame.subarray((2 * 8 + 6) * 4, (2 * 8 + 7) * 4)], [0, 0, 255, 255])
+  t.deepEqual([...frame.subarray((1 * 8 + 2) * 4, (1 * 8 + 3) * 4)], [0, 0, 0, 0])
+  t.deepEqual([...frame.subarray((3 * 8 + 3) * 4, (3 * 8 + 4) * 4)], [0, 0, 0, 0])
+
+  const error = t.throws(() => resvg.renderInto(frame, { stride: 8 * 4, x: 5, y: 0 }))
+  t.is(error?.message, 'The target buffer is too small for a 4x2 render at (5, 0)')
+})
+
+test('should render a single node by id', (t) => {
+  const svg = `<svg width="100px" height="100px" viewBox="0 0 100 100" version="1.1" xmlns="http://www.w3.org/2000/svg">
+    <g id="first"><rect fill="red" x="10" y="10" width="20" height="10"></rect></g>
+    <g id="second"><rect fill="#00f" x="50" y="50" width="40" height="40"></rect></g>
+  </svg>`
+  const resvg = new Resvg(svg)
+
+  const first = resvg.renderNode('first')
+  t.is(first.width, 20)
+  t.is(first.height, 10)
+  t.deepEqual([...first.pixels.subarray(0, 4)], [255, 0, 0, 255])
+
+  const second = resvg.renderNode('second', { fitTo: { mode: 'width', value: 80 } })
+  t.is(second.width, 80)
+  t.is(second.height, 80)
+  t.deepEqual([...second.pixels.subarray(0, 4)], [0, 0, 255, 255])
+
+  const error = t.throws(() => resvg.renderNode('missing'))
+  t.is(error?.message, 'No element with id `missing` found, note that unused <symbol> elements are not kept')
+})
+
+test('should fit into a box', (t) => {
+  const svg = `<svg width="200px" height="100px" viewBox="0 0 200 100" version="1.1" xmlns="http://www.w3.org/2000/svg">
+    <rect fill="red" x="0" y="0" width="100" height="100"></rect>
+    <rect fill="#0f0" x="100" y="0" width="100" height="100"></rect>
+  </svg>`
+  const pixel = (image: { pixels: Buffer; width: number }, x: number, y: number) =>
+    [...image.pixels.subarray((y * image.width + x) * 4, (y * image.width + x) * 4 + 4)]
+
+  const contain = new Resvg(svg, {
+    background: '#00f',
+    fitTo: { mode: 'box', value: { width: 100, height: 100 } },
+  }).render()
+  t.is(contain.width, 100)
+  t.is(contain.height, 100)
+  t.deepEqual(pixel(contain, 50, 10), [0, 0, 255, 255])
+  t.deepEqual(pixel(contain, 10, 50), [255, 0, 0, 255])
+  t.deepEqual(pixel(contain, 50, 90), [0, 0, 255, 255])
+
+  const cover = new Resvg(svg, {
+    fitTo: { mode: 'box', value: { width: 100, height: 100, fit: 'cover', align: 'xMaxYMid' } },
+  }).render()
+  t.is(cover.width, 100)
+  t.deepEqual(pixel(cover, 50, 50), [0, 255, 0, 255])
+
+  const inside = new Resvg(svg, { fitTo: { mode: 'box', value: { width: 100, height: 100, fit: 'inside' } } }).render()
+  t.is(inside.width, 100)
+  t.is(inside.height, 50)
+
+  const fill = new Resvg(svg, { fitTo: { mode: 'box', value: { width: 100, height: 100, fit: 'fill' } } }).render()
+  t.is(fill.height, 100)
+  t.deepEqual(pixel(fill, 10, 90), [255, 0, 0, 255])
+})
+
+test('should render many sizes from one parse', (t) => {
+  const svg = `<svg width="20px" height="10px" viewBox="0 0 20 10" version="1.1" xmlns="http://www.w3.org/2000/svg">
+    <rect fill="red" x="0" y="0" width="20" height="10"></rect>
+  </svg>`
+  const resvg = new Resvg(svg, { background: '#00f' })
+  const [x1, x2, x3, cropped] = resvg.renderMany([
+    {},
+    { fitTo: { mode: 'zoom', value: 2 } },
+    { fitTo: { mode: 'zoom', value: 3 }, background: 'transparent' },
+    { crop: { left: 0, top: 0, right: 5, bottom: 5 } },
+  ])
+
+  t.is(x1.width, 20)
+  t.is(x2.width, 40)
+  t.is(x2.height, 20)
+  t.is(x3.width, 60)
+  t.is(cropped.width, 5)
+  t.is(cropped.height, 5)
+  t.true(x1.pixels.equals(resvg.render().pixels))
+
+  // The cached render tree is rebuilt after cropping
+  resvg.cropByBBox({ x: 0, y: 0, width: 10, height: 10 })
+  t.is(resvg.render().width, 10)
+  t.is(resvg.renderMany([{}])[0].width, 10)
+
+  // @ts-expect-error
+  const invalid = t.throws<ResvgError>(() => resvg.renderMany([{}, { fitTo: { mode: 'widht', value: 2 } }]))
+  t.is(invalid?.code, 'ERR_INVALID_OPTIONS')
+  t.regex(invalid?.path ?? '', /^\[1\]\.fitTo/)
+})
+
+test('should share a FontDatabase across instances', async (t) => {
+  const svg = `
+  <svg xmlns="http://www.w3.org/2000/svg" width="200" height="200" viewBox="0 0 200 200">
+    <text fill="blue" font-family="serif" font-size="120">
+      <tspan x="40" y="143">水</tspan>
+    </text>
+  </svg>
+  `
+  const fontDb = new FontDatabase({
+    fontFiles: ['./example/SourceHanSerifCN-Light-subset.ttf'],
+    loadSystemFonts: false,
+  })
+  const countBlue = (pixels: Buffer) => pixels.toJSON().data.join(',').match(/0,0,255/g)?.length
+
+  const first = new Resvg(svg, {}, fontDb).render()
+  const second = new Resvg(svg, { font: { loadSystemFonts: true } }, fontDb).render()
+  t.is(countBlue(first.pixels), 1727)
+  t.true(second.pixels.equals(first.pixels))
+
+  const asyncImage = await renderAsync(svg, null, null, fontDb)
+  t.true(asyncImage.pixels.equals(first.pixels))
+})
+
+test('should resolve generic font families to their configured fonts', (t) => {
+  const render = (family: string) => {
+    const svg = `<svg xmlns="http://www.w3.org/2000/svg" width="300" height="100" viewBox="0 0 300 100">
+      <text x="10" y="70" fill="blue" font-family="${family}" font-size="60">Hello</text>
+    </svg>`
+    const resvg = new Resvg(svg, {
+      font: {
+        fontFiles: ['./__test__/Pacifico-Regular.ttf', './example/SourceHanSerifCN-Light-subset.ttf'],
+        loadSystemFonts: false,
+        defaultFontFamily: 'Source Han Serif CN Light',
+        monospaceFamily: 'Pacifico',
+        serifFamily: 'Not Loaded Family',
+      },
+    })
+    return resvg.render().pixels
+  }
+
+  // `monospace` uses its configured family.
+  t.deepEqual(render('monospace'), render('Pacifico'))
+  // `serif` falls back to the default family because its configured family is not loaded.
+  t.deepEqual(render('serif'), render('Source Han Serif CN Light'))
+  t.notDeepEqual(render('monospace'), render('serif'))
+})
+
+test('should render serif and sans-serif with their own configured fonts', (t) => {
+  const svg = `<svg xmlns="http://www.w3.org/2000/svg" width="300" height="200" viewBox="0 0 300 200">
+    <text id="serif" x="10" y="70" fill="blue" font-family="serif" font-size="60">水 Hello</text>
+    <text id="sans-serif" x="10" y="170" fill="blue" font-family="sans-serif" font-size="60">水 Hello</text>
+  </svg>`
+  const font = {
+    fontFiles: ['./__test__/Pacifico-Regular.ttf', './example/SourceHanSerifCN-Light-subset.ttf'],
+    loadSystemFonts: false,
+    defaultFontFamily: 'Pacifico',
+    serifFamily: 'Source Han Serif CN Light',
+    sansSerifFamily: 'Pacifico',
+  }
+  const resvg = new Resvg(svg, { font })
+
+  // Both families used to resolve to `defaultFontFamily`.
+  const [serif, sansSerif] = resvg.fontReport()
+  t.is(serif.face?.family, 'Source Han Serif CN Light')
+  t.is(sansSerif.face?.family, 'Pacifico')
+
+  const explicit = svg.replace('"serif"', '"Source Han Serif CN Light"').replace('"sans-serif"', '"Pacifico"')
+  t.deepEqual(resvg.render().pixels, new Resvg(explicit, { font }).render().pixels)
+})
+
+test('should load fontBuffers and WOFF/WOFF2 fonts', async (t) => {
+  const svg = `
+  <svg xmlns="http://www.w3.org/2000/svg" width="500" height="200" viewBox="0 0 500 200">
+    <text fill="blue" font-size="60">
+      <tspan x="40" y="100">Hello resvg-js</tspan>
+    </text>
+  </svg>
+  `
+  const render = (font: ResvgRenderOptions['font']) => {
+    const resvg = new Resvg(svg, { font: { loadSystemFonts: false, ...font } })
+    return resvg.render().pixels
+  }
+  const expected = render({ fontFiles: ['./__test__/Pacifico-Regular.ttf'] })
+
+  const ttf = await fs.readFile(join(__dirname, './Pacifico-Regular.ttf'))
+  const woff = await fs.readFile(join(__dirname, './Pacifico-Regular.woff'))
+  const woff2 = await fs.readFile(join(__dirname, '../wasm/fonts/Pacifico-Regular.woff2'))
+  t.deepEqual(render({ fontBuffers: [ttf] }), expected)
+  t.deepEqual(render({ fontBuffers: [woff] }), expected)
+  t.deepEqual(render({ fontBuffers: [woff2] }), expected)
+  t.deepEqual(render({ fontFiles: ['./__test__/Pacifico-Regular.woff'] }), expected)
+  t.deepEqual(render({ fontFiles: ['./wasm/fonts/Pacifico-Regular.woff2'] }), expected)
+  // The directory only has the WOFF2 font.
+  t.deepEqual(render({ fontDirs: ['./wasm/fonts'] }), expected)
+
+  const image = await renderAsync(svg, { font: { loadSystemFonts: false, fontBuffers: [woff] } })
+  t.deepEqual(image.pixels, expected)
+  const fontDb = new FontDatabase({ loadSystemFonts: false, fontBuffers: [woff2] })
+  t.deepEqual(new Resvg(svg, null, fontDb).render().pixels, expected)
+})
+
+test('should take missing glyphs from fallbackFamilies', (t) => {
+  const svg = `<svg xmlns="http://www.w3.org/2000/svg" width="400" height="120" viewBox="0 0 400 120">
+    <text x="10" y="80" fill="blue" font-family="Source Han Serif CN Light" font-size="60">水 Hello</text>
+  </svg>`
+  const render = (fontFiles: string[], fallbackFamilies?: string[] | { [script: string]: string[] }) => {
+    const resvg = new Resvg(svg, { font: { fontFiles, loadSystemFonts: false, fallbackFamilies } })
+    return Array.from(resvg.render().pixels)
+  }
+  const sourceHan = './example/SourceHanSerifCN-Light-subset.ttf'
+  const withoutFallback = render([sourceHan])
+  const withFallback = render([sourceHan, './__test__/Pacifico-Regular.ttf'], ['Pacifico'])
+
+  // The Latin letters are missing from Source Han, they are taken from Pacifico.
+  t.true(
+    withFallback.join(',').match(/0,0,255/g)!.length > (withoutFallback.join(',').match(/0,0,255/g)?.length ?? 0),
+  )
+  t.deepEqual(render([sourceHan, './__test__/Pacifico-Regular.ttf'], { Latin: ['Pacifico'] }), withFallback)
+  t.deepEqual(render([sourceHan, './__test__/Pacifico-Regular.ttf'], { default: ['Pacifico'] }), withFallback)
+
+  // The missing characters are rendered as if their span had the fallback family.
+  const explicit = new Resvg(svg.replace('水 Hello', '水 <tspan font-family="Pacifico">Hello</tspan>'), {
+    font: { fontFiles: [sourceHan, './__test__/Pacifico-Regular.ttf'], loadSystemFonts: false },
+  })
+  t.deepEqual(
+    render([sourceHan, './__test__/Pacifico-Regular.ttf'], { Han: ['Source Han Serif CN Light'], Latin: ['Pacifico'] }),
+    Array.from(explicit.render().pixels),
+  )
+})
+
+test('should report the resolved fonts and missing glyphs', async (t) => {
+  const svg = `<svg xmlns="http://www.w3.org/2000/svg" width="400" height="200" viewBox="0 0 400 200">
+    <text id="latin" x="10" y="80" font-family="Pacifico, serif" font-size="40">Hi 水&#x1F600;</text>
+    <text id="han" x="10" y="160" font-family="Source Han Serif CN Light" font-size="40">水</text>
+  </svg>`
+  const pacifico = await fs.readFile(join(__dirname, './Pacifico-Regular.ttf'))
+  const resvg = new Resvg(svg, {
+    font: {
+      fontFiles: ['./example/SourceHanSerifCN-Light-subset.ttf'],
+      fontBuffers: [pacifico],
+      loadSystemFonts: false,
+    },
+  })
+  const [latin, han] = resvg.fontReport()
+
+  t.is(latin.elementId, 'latin')
+  t.deepEqual(latin.families, ['Pacifico', 'serif'])
+  t.is(latin.face?.family, 'Pacifico')
+  t.is(latin.face?.bufferIndex, 0)
+  t.is(latin.face?.path, null)
+  // `水` is taken from Source Han, only the emoji is missing from every font.
+  t.deepEqual(latin.missingCodePoints, [0x1f600])
+
+  t.is(han.elementId, 'han')
+  t.is(han.face?.family, 'Source Han Serif CN Light')
+  t.is(han.face?.path, './example/SourceHanSerifCN-Light-subset.ttf')
+  t.is(han.face?.weight, 300)
+  t.deepEqual(han.missingCodePoints, [])
+
+  // A run taken from a fallback family reports the fallback face.
+  const withFallback = new Resvg(svg, {
+    font: {
+      fontFiles: ['./example/SourceHanSerifCN-Light-subset.ttf'],
+      fontBuffers: [pacifico],
+      loadSystemFonts: false,
+      fallbackFamilies: ['Source Han Serif CN Light'],
+    },
+  })
+  const [hi, water, emoji] = withFallback.fontReport()
+  t.is(hi.text, 'Hi ')
+  t.is(hi.face?.family, 'Pacifico')
+  t.is(water.text, '水')
+  t.deepEqual(water.families, ['Pacifico', 'serif'])
+  t.is(water.face?.family, 'Source Han Serif CN Light')
+  t.deepEqual(water.missingCodePoints, [])
+  t.is(emoji.face?.family, 'Pacifico')
+  t.deepEqual(emoji.missingCodePoints, [0x1f600])
+})
+
+test('should list the loaded fonts', async (t) => {
+  const pacifico = await fs.readFile(join(__dirname, './Pacifico-Regular.ttf'))
+  const fonts = listFonts({
+    fontFiles: ['./example/SourceHanSerifCN-Light-subset.ttf'],
+    fontBuffers: [pacifico],
+    loadSystemFonts: false,
+  })
+
+  t.is(fonts.length, 2)
+  const [sourceHan, pacificoFace] = fonts
+  t.deepEqual(sourceHan.families[0], { name: 'Source Han Serif CN Light', language: 'English (United States)' })
+  t.is(sourceHan.postScriptName, 'SourceHanSerifCN-Light')
+  t.is(sourceHan.weight, 300)
+  t.is(sourceHan.style, 'normal')
+  t.is(sourceHan.stretch, 'normal')
+  t.false(sourceHan.monospaced)
+  t.is(sourceHan.path, './example/SourceHanSerifCN-Light-subset.ttf')
+  t.is(sourceHan.index, 0)
+  t.is(pacificoFace.postScriptName, 'Pacifico-Regular')
+  t.is(pacificoFace.path, null)
+  t.is(pacificoFace.bufferIndex, 0)
+
+  const fontDb = new FontDatabase({ fontBuffers: [pacifico], loadSystemFonts: false })
+  t.deepEqual(fontDb.listFonts(), [pacificoFace])
+})
+
+test('should throw because of invalid options, with the path of the invalid value', async (t) => {
+  const svg = `<svg width="100" height="100" xmlns="http://www.w3.org/2000/svg"><rect width="100" height="100" fill="red"/></svg>`
+  const error = t.throws(
+    () => {
+      // @ts-expect-error
+      new Resvg(svg, { fitTo: { mode: 'widht', value: 200 } })
+    },
+    { instanceOf: Error },
+  )
+  t.regex(error.message, /^Invalid options at `fitTo[^`]*`: unknown variant `widht`/)
+
+  const unknownField = t.throws(
+    () => {
+      // @ts-expect-error
+      new Resvg(svg, { backgroud: '#fff' })
+    },
+    { instanceOf: Error },
+  )
+  t.regex(unknownField.message, /^Invalid options at `\.`: unknown field `backgroud`/)
+
+  // @ts-expect-error
+  await t.throwsAsync(renderAsync(svg, { logLevel: 'verbose' }), { message: /^Invalid options at `logLevel`/ })
+
+  // The JSON string form is still accepted.
+  // @ts-expect-error
+  const resvg = new Resvg(svg, JSON.stringify({ fitTo: { mode: 'width', value: 200 } }))
+  t.is(resvg.render().width, 200)
+})
+
+test('should treat undefined and null options as not set', async (t) => {
+  const svg = '<svg width="10" height="10" xmlns="http://www.w3.org/2000/svg"></svg>'
+  const opts: { background?: string } = {}
+  const options: ResvgRenderOptions = {
+    fitTo: undefined,
+    background: opts.background,
+    // @ts-expect-error
+    crop: null,
+    font: { loadSystemFonts: false, defaultFontFamily: undefined },
+  }
+
+  const resvg = new Resvg(svg, options)
+  t.is(resvg.render().width, 10)
+  t.is((await renderAsync(svg, options)).width, 10)
+  // @ts-expect-error
+  t.true(resvg.render().asPng({ text: undefined, srgb: null }).length > 0)
+})
+
+test('should expose a stable error code and the position of parse errors', async (t) => {
+  const malformed = `<svg width="100" height="100" xmlns="http://www.w3.org/2000/svg">
+  <rect width="100" height="100">
+</svg>`
+  const parseError = t.throws<ResvgError>(() => new Resvg(malformed), { instanceOf: Error })
+  t.is(parseError?.code, 'ERR_SVG_PARSE')
+  t.is(parseError?.line, 3)
+  t.is(typeof parseError?.column, 'number')
+
+  const asyncError = await t.throwsAsync<ResvgError>(renderAsync(malformed))
+  t.is(asyncError?.code, 'ERR_SVG_PARSE')
+  t.is(asyncError?.line, 3)
+
+  const svg = '<svg width="128" height="128" xmlns="http://www.w3.org/2000/svg"></svg>'
+  const zeroSized = t.throws<ResvgError>(() => new Resvg(svg, { fitTo: { mode: 'zoom', value: 0 } }).render())
+  t.is(zeroSized?.code, 'ERR_ZERO_SIZED')
+
+  const notFound = t.throws<ResvgError>(() => new Resvg(svg).renderNode('missing'))
+  t.is(notFound?.code, 'ERR_NODE_NOT_FOUND')
+  t.is(notFound?.elementId, 'missing')
+
+  // @ts-expect-error
+  const invalidOptions = t.throws<ResvgError>(() => new Resvg(svg, { fitTo: { mode: 'widht', value: 1 } }))
+  t.is(invalidOptions?.code, 'ERR_INVALID_OPTIONS')
+  t.true(invalidOptions?.path?.startsWith('fitTo'))
+})
+
+test('should throw because of invalid output options', (t) => {
+  const svg = '<svg width="10" height="10" xmlns="http://www.w3.org/2000/svg"></svg>'
+  const resvg = new Resvg(svg, { font: { loadSystemFonts: false } })
+  const image = resvg.render()
+
+  // @ts-expect-error
+  const png = t.throws<ResvgError>(() => image.asPng({ compression: 'smallest' }))
+  t.is(png?.code, 'ERR_INVALID_OPTIONS')
+  t.is(png?.path, 'compression')
+  // @ts-expect-error
+  t.is(t.throws<ResvgError>(() => image.asJpeg({ qualty: 50 }))?.code, 'ERR_INVALID_OPTIONS')
+  // @ts-expect-error
+  t.is(t.throws<ResvgError>(() => image.getPixels({ format: 'rgb565' }))?.path, 'format')
+  // @ts-expect-error
+  t.is(t.throws<ResvgError>(() => resvg.renderNode('a', { fitto: {} }))?.code, 'ERR_INVALID_OPTIONS')
+  const buffer = Buffer.alloc(10 * 10 * 4)
+  t.is(t.throws<ResvgError>(() => resvg.renderInto(buffer, { x: -1 }))?.path, 'x')
+  // @ts-expect-error
+  t.is(t.throws<ResvgError>(() => new FontDatabase({ loadSystemFont: false }))?.code, 'ERR_INVALID_OPTIONS')
+})
+
+test('should collect the warnings of each instance', (t) => {
+  const svg = `<svg width="100" height="100" xmlns="http://www.w3.org/2000/svg">
+  <rect id="r1" width="-5" height="10"/>
+</svg>`
+  // Warnings are collected whatever the `logLevel` is.
+  const resvg = new Resvg(svg, { logLevel: 'off', font: { loadSystemFonts: false } })
+  t.deepEqual(
+    resvg.warnings.find((w) => w.elementId === 'r1'),
+    { level: 'warn', message: "Rect 'r1' has an invalid 'width' value. Skipped.", elementId: 'r1' },
+  )
+
+  const valid = new Resvg('<svg width="10" height="10" xmlns="http://www.w3.org/2000/svg"></svg>', {
+    font: { loadSystemFonts: false },
+  })
+  valid.render()
+  t.false(valid.warnings.some((w) => w.elementId === 'r1'))
+
+  // The features the PDF export skips are reported too.
+  const filtered = new Resvg(
+    `<svg width="100" height="100" xmlns="http://www.w3.org/2000/svg">
+  <filter id="blur"><feGaussianBlur stdDeviation="5"/></filter>
+  <g id="g1" filter="url(#blur)"><rect width="100" height="100"/></g>
+</svg>`,
+    { font: { loadSystemFonts: false } },
+  )
+  filtered.toPdf()
+  t.true(filtered.warnings.some((w) => w.message.startsWith('PDF export does not support filters')))
+})
+
+test('should take the element id from the warnings that name one', (t) => {
+  const svg = `<svg width="100" height="100" xmlns="http://www.w3.org/2000/svg">
+  <defs>
+    <pattern id="pt" width="0" height="10"/>
+    <mask id="m1" width="0"/>
+    <filter id="f1" width="0"><feFlood/></filter>
+  </defs>
+  <circle id="c1" r="-1"/>
+  <ellipse id="e1" rx="0" ry="0"/>
+  <polyline id="p1" points="0,0"/>
+  <rect width="10" height="10" fill="url(#pt)"/>
+  <rect width="10" height="10" mask="url(#m1)"/>
+  <rect width="10" height="10" filter="url(#f1)"/>
+</svg>`
+  const resvg = new Resvg(svg, { font: { loadSystemFonts: false } })
+  const messages = Object.fromEntries(resvg.warnings.map((w) => [w.elementId, w.message]))
+  t.is(messages.c1, "Circle 'c1' has an invalid 'r' value. Skipped.")
+  t.is(messages.e1, "Ellipse 'e1' has an invalid 'rx' value. Skipped.")
+  t.is(messages.p1, "Polyline 'p1' has less than 2 points. Skipped.")
+  t.is(messages.pt, "Pattern 'pt' has an invalid size. Skipped.")
+  t.is(messages.m1, "Mask 'm1' has an invalid size. Skipped.")
+  t.is(messages.f1, "Filter 'f1' has an invalid region. Skipped.")
+})
+
+test.serial('should pass the log records to the logger set with setLogger()', async (t) => {
+  const svg = `<svg width="100" height="100" xmlns="http://www.w3.org/2000/svg">
+  <rect id="logged" width="-5" height="10"/>
+</svg>`
+  const logged = new Promise<[string, string, string]>((resolve) => {
+    setLogger((level, target, message) => {
+      if (message.includes("'logged'")) {
+        resolve([level, target, message])
+      }
+    })
+  })
+  try {
+    await renderAsync(svg, { logLevel: 'warn', font: { loadSystemFonts: false } })
+    const [level, target, message] = await logged
+    t.is(level, 'warn')
+    t.true(target.startsWith('usvg'))
+    t.is(message, "Rect 'logged' has an invalid 'width' value. Skipped.")
+  } finally {
+    setLogger(null)
+  }
+})
+
+test.serial('should report the errors thrown by the logger set with setLogger()', async (t) => {
+  const svg = `<svg width="100" height="100" xmlns="http://www.w3.org/2000/svg">
+  <rect id="throwing" width="-5" height="10"/>
+</svg>`
+  const consoleError = console.error
+  const reported = new Promise<unknown[]>((resolve) => {
+    console.error = (...args: unknown[]) => resolve(args)
+  })
+  setLogger(() => {
+    throw new Error('logger failed')
+  })
+  try {
+    await renderAsync(svg, { logLevel: 'warn', font: { loadSystemFonts: false } })
+    const [message, error] = await reported
+    t.is(message, 'resvg-js: the logger set with setLogger() threw')
+    t.is((error as Error).message, 'logger failed')
+  } finally {
+    setLogger(null)
+    console.error = consoleError
+  }
+})
+
+test('should enforce the resource limits', (t) => {
+  const svg = '<svg width="100" height="100" xmlns="http://www.w3.org/2000/svg"><rect width="100" height="100"/></svg>'
+  const zoom = t.throws<ResvgError>(() =>
+    new Resvg(svg, { fitTo: { mode: 'zoom', value: 1000 }, limits: { maxPixels: 4096 * 4096 } }).render(),
+  )
+  t.is(zoom?.code, 'ERR_LIMIT_PIXELS')
+  const wide = t.throws<ResvgError>(() => new Resvg(svg, { limits: { maxWidth: 50 } }).render())
+  t.is(wide?.code, 'ERR_LIMIT_WIDTH')
+  t.is(new Resvg(svg, { limits: { maxPixels: 100 * 100, maxWidth: 100, maxHeight: 100 } }).render().width, 100)
+
+  // Each group uses the previous one twice, 2^30 rectangles once expanded.
+  let groups = '<g id="g0"><rect width="1" height="1"/></g>'
+  for (let i = 1; i <= 30; i++) {
+    groups += `<g id="g${i}"><use xlink:href="#g${i - 1}"/><use xlink:href="#g${i - 1}"/></g>`
+  }
+  const laughs = `<svg width="100" height="100" xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink">
+  <defs>${groups}</defs>
+  <use xlink:href="#g30"/>
+</svg>`
+  const nodes = t.throws<ResvgError>(() => new Resvg(laughs, { limits: { maxNodes: 10000 } }))
+  t.is(nodes?.code, 'ERR_LIMIT_NODES')
+  const depth = t.throws<ResvgError>(() => new Resvg(laughs, { limits: { maxUseDepth: 5 } }))
+  t.is(depth?.code, 'ERR_LIMIT_USE_DEPTH')
+
+  // A chain of 5000 `<use>` elements is measured without exhausting the stack.
+  let chain = '<g id="c0"><rect width="1" height="1"/></g>'
+  for (let i = 1; i <= 5000; i++) {
+    chain += `<g id="c${i}"><use xlink:href="#c${i - 1}"/></g>`
+  }
+  const long = `<svg width="100" height="100" xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink">
+  <defs>${chain}</defs>
+</svg>`
+  t.is(t.throws<ResvgError>(() => new Resvg(long, { limits: { maxUseDepth: 100 } }))?.code, 'ERR_LIMIT_USE_DEPTH')
+
+  const filtered = `<svg width="100" height="100" xmlns="http://www.w3.org/2000/svg">
+  <filter id="blur"><feGaussianBlur stdDeviation="5"/></filter>
+  <rect width="100" height="100" filter="url(#blur)"/>
+</svg>`
+  const filterRegion = t.throws<ResvgError>(() => new Resvg(filtered, { limits: { maxFilterRegion: 1000 } }).render())
+  t.is(filterRegion?.code, 'ERR_LIMIT_FILTER_REGION')
+})
+
 test('should throw because invalid SVG attribute (width attribute is 0)', (t) => {
   const error = t.throws(
     () => {
@@ -6,7 +6,7 @@ import test from 'ava'
 import jimp from 'jimp-compact'
 import fetch from 'node-fetch'
 
-import { Resvg, initWasm } from '../wasm'
+import { FontDatabase, Resvg, ResvgError, initWasm, listFonts } from '../wasm'
 
 import { jimpToRgbaPixels } from './helper'
 
@@ -505,6 +505,489 @@ test('should render using font buffer provided by options', async (t) => {
 })
 
 // throws
+test('should encode WebP', (t) => {
+  const svg = `<svg width="10px" height="5px" viewBox="0 0 10 5" version="1.1" xmlns="http://www.w3.org/2000/svg">
+    <rect fill="red" x="0" y="0" width="5" height="5"></rect>
+    <rect fill="green" x="5" y="0" width="5" height="5"></rect>
+  </svg>`
+  const resvg = new Resvg(svg)
+  const webp = Buffer.from(resvg.render().asWebp({ lossless: true }))
+  const lossy = Buffer.from(resvg.render().asWebp({ quality: 50 }))
+
+  t.is(webp.toString('ascii', 0, 4), 'RIFF')
+  t.is(webp.toString('ascii', 8, 12), 'WEBP')
+  t.is(webp.toString('ascii', 12, 16), 'VP8L')
+  t.is(lossy.toString('ascii', 12, 16), 'VP8 ')
+})
+
+test('should encode JPEG', async (t) => {
+  const svg = `<svg width="10px" height="5px" viewBox="0 0 10 5" version="1.1" xmlns="http://www.w3.org/2000/svg">
+    <rect fill="red" x="0" y="0" width="5" height="5"></rect>
+  </svg>`
+  const resvg = new Resvg(svg)
+  const jpegBuffer = Buffer.from(resvg.render().asJpeg({ progressive: true }))
+  const result = await jimp.read(jpegBuffer)
+
+  t.is(result.getWidth(), 10)
+  t.is(result.getHeight(), 5)
+  // Transparent pixels are flattened onto white
+  const pixel = jimp.intToRGBA(result.getPixelColor(8, 2))
+  t.true(pixel.r > 240 && pixel.g > 240 && pixel.b > 240)
+})
+
+test('should encode AVIF', (t) => {
+  const svg = `<svg width="16px" height="16px" viewBox="0 0 16 16" version="1.1" xmlns="http://www.w3.org/2000/svg">
+    <circle fill="red" cx="8" cy="8" r="6"></circle>
+  </svg>`
+  const resvg = new Resvg(svg)
+  const avif = Buffer.from(resvg.render().asAvif({ speed: 10 }))
+
+  t.is(avif.toString('ascii', 4, 8), 'ftyp')
+  t.is(avif.toString('ascii', 8, 12), 'avif')
+})
+
+test('should export a vector PDF', (t) => {
+  const svg = `<svg width="200px" height="100px" viewBox="0 0 200 100" version="1.1" xmlns="http://www.w3.org/2000/svg">
+    <rect fill="#FCA6A6" opacity="0.5" x="0" y="0" width="200" height="100"></rect>
+  </svg>`
+  const resvg = new Resvg(svg)
+  const pdf = Buffer.from(resvg.toPdf()).toString('latin1')
+
+  t.true(pdf.startsWith('%PDF-'))
+  t.true(pdf.includes('/MediaBox [0 0 200 100]'))
+})
+
+test('should write PNG metadata chunks', (t) => {
+  const svg = `<svg width="16px" height="16px" viewBox="0 0 16 16" version="1.1" xmlns="http://www.w3.org/2000/svg">
+    <circle fill="red" cx="8" cy="8" r="6"></circle>
+  </svg>`
+  const resvg = new Resvg(svg, { dpi: 144 })
+  const png = Buffer.from(resvg.render().asPng({ writeDpi: true, text: { Title: 'resvg' } }))
+
+  const phys = png.indexOf('pHYs')
+  t.true(phys > 0)
+  t.is(png.readUInt32BE(phys + 4), 5669)
+  t.true(png.includes('tEXtTitle\0resvg'))
+})
+
+test('should encode PNG with compression and filter options', async (t) => {
+  const svg = `<svg width="64px" height="64px" viewBox="0 0 64 64" version="1.1" xmlns="http://www.w3.org/2000/svg">
+    <circle fill="red" cx="32" cy="32" r="24"></circle>
+  </svg>`
+  const resvg = new Resvg(svg)
+  const pngData = resvg.render()
+  const fast = Buffer.from(pngData.asPng({ compression: 'fast', filter: 'none' }))
+  const max = Buffer.from(pngData.asPng({ compression: 'max', filter: 'paeth' }))
+
+  t.true(max.length < fast.length)
+  const fastImage = await jimp.read(fast)
+  const maxImage = await jimp.read(max)
+  t.true(maxImage.bitmap.data.equals(fastImage.bitmap.data))
+})
+
+test('should encode palette PNG', async (t) => {
+  const svg = `<svg width="32px" height="32px" viewBox="0 0 32 32" version="1.1" xmlns="http://www.w3.org/2000/svg">
+    <rect fill="#00f" x="0" y="0" width="16" height="32"></rect>
+    <rect fill="red" fill-opacity="0.5" x="16" y="0" width="16" height="16"></rect>
+  </svg>`
+  const resvg = new Resvg(svg, { shapeRendering: 1 })
+  const pngData = resvg.render()
+  const indexed = Buffer.from(pngData.asPng({ palette: true, maxColors: 16 }))
+
+  t.is(indexed[25], 3)
+  t.true(indexed.includes('tRNS'))
+  const rgbaImage = await jimp.read(Buffer.from(pngData.asPng()))
+  const indexedImage = await jimp.read(indexed)
+  t.true(indexedImage.bitmap.data.equals(rgbaImage.bitmap.data))
+})
+
+test('should get unpremultiplied pixels in other channel orders', (t) => {
+  const svg = `<svg width="2px" height="2px" viewBox="0 0 2 2" version="1.1" xmlns="http://www.w3.org/2000/svg">
+    <rect fill="rgb(200, 100, 50)" fill-opacity="0.5" x="0" y="0" width="2" height="2"></rect>
+  </svg>`
+  const resvg = new Resvg(svg)
+  const pngData = resvg.render()
+
+  const straight = pngData.getPixels({ premultiplied: false })
+  t.true(Math.abs(straight[0] - 200) <= 1)
+  t.is(straight[3], 128)
+  const bgra = pngData.getPixels({ premultiplied: false, format: 'bgra' })
+  t.deepEqual([...bgra.subarray(0, 4)], [straight[2], straight[1], straight[0], straight[3]])
+  t.is(pngData.getPixels({ format: 'gray' }).length, 2 * 2)
+})
+
+test('should render into an existing buffer', (t) => {
+  const svg = `<svg width="4px" height="2px" viewBox="0 0 4 2" version="1.1" xmlns="http://www.w3.org/2000/svg">
+    <rect fill="#00f" x="0" y="0" width="4" height="2"></rect>
+  </svg>`
+  const resvg = new Resvg(svg)
+
+  const frame = new Uint8Array(8 * 4 * 4)
+  resvg.renderInto(frame, { stride: 8 * 4, x: 3, y: 1 })
+  t.deepEqual([...frame.subarray((1 * 8 + 3) * 4, (1 * 8 + 4) * 4)], [0, 0, 255, 255])
+  t.deepEqual([...frame.subarray((1 * 8 + 2) * 4, (1 * 8 + 3) * 4)], [0, 0, 0, 0])
+
+  const error = t.throws(() => resvg.renderInto(frame, { stride: 6 }))
+  t.is(error?.message, 'The stride of the target buffer must be a positive multiple of 4, got 6')
+})
+
+test('should render a single node by id', (t) => {
+  const svg = `<svg width="100px" height="100px" viewBox="0 0 100 100" version="1.1" xmlns="http://www.w3.org/2000/svg">
+    <g id="first"><rect fill="red" x="10" y="10" width="20" height="10"></rect></g>
+    <g id="second"><rect fill="#00f" x="50" y="50" width="40" height="40"></rect></g>
+  </svg>`
+  const resvg = new Resvg(svg)
+
+  const second = resvg.renderNode('second', { fitTo: { mode: 'zoom', value: 2 } })
+  t.is(second.width, 80)
+  t.is(second.height, 80)
+  t.deepEqual([...second.pixels.subarray(0, 4)], [0, 0, 255, 255])
+  t.throws(() => resvg.renderNode('missing'))
+})
+
+test('should fit into a box', (t) => {
+  const svg = `<svg width="200px" height="100px" viewBox="0 0 200 100" version="1.1" xmlns="http://www.w3.org/2000/svg">
+    <rect fill="red" x="0" y="0" width="200" height="100"></rect>
+  </svg>`
+  const contain = new Resvg(svg, {
+    background: '#00f',
+    fitTo: { mode: 'box', value: { width: 100, height: 100, fit: 'contain', align: 'xMidYMin' } },
+  }).render()
+  t.is(contain.width, 100)
+  t.is(contain.height, 100)
+  // Aligned to the top, letterboxed at the bottom
+  t.deepEqual([...contain.pixels.subarray(0, 4)], [255, 0, 0, 255])
+  t.deepEqual([...contain.pixels.subarray((99 * 100) * 4, (99 * 100) * 4 + 4)], [0, 0, 255, 255])
+})
+
+test('should render many sizes from one parse', (t) => {
+  const svg = `<svg width="20px" height="10px" viewBox="0 0 20 10" version="1.1" xmlns="http://www.w3.org/2000/svg">
+    <rect fill="red" x="0" y="0" width="20" height="10"></rect>
+  </svg>`
+  const resvg = new Resvg(svg)
+  const images = resvg.renderMany([{}, { fitTo: { mode: 'zoom', value: 2 } }])
+
+  t.is(images.length, 2)
+  t.is(images[0].width, 20)
+  t.is(images[1].width, 40)
+  t.is(images[1].height, 20)
+
+  // @ts-expect-error
+  const invalid = t.throws<ResvgError>(() => resvg.renderMany([{}, { fitTo: { mode: 'widht', value: 2 } }]))
+  t.is(invalid?.code, 'ERR_INVALID_OPTIONS')
+  t.regex(invalid?.path ?? '', /^\[1\]\.fitTo/)
+})
+
+test('should share a FontDatabase across instances', async (t) => {
+  const svg = `
+  <svg xmlns="http://www.w3.org/2000/svg" width="200" height="200" viewBox="0 0 200 200">
+    <text fill="blue" font-family="serif" font-size="120">
+      <tspan x="40" y="143">水</tspan>
+    </text>
+  </svg>
+  `
+  const fontBuffer = await fs.readFile(join(__dirname, '../example/SourceHanSerifCN-Light-subset.ttf'))
+  const fontDb = new FontDatabase({ fontBuffers: [fontBuffer] })
+
+  const first = new Resvg(svg, {}, fontDb).render()
+  const second = new Resvg(svg, {}, fontDb).render()
+  t.is(Array.from(first.pixels).join(',').match(/0,0,255/g)?.length, 1727)
+  t.deepEqual(second.pixels, first.pixels)
+  // The handle is borrowed, not consumed by the instances.
+  t.true(new Resvg(svg, {}, fontDb) instanceof Resvg)
+  t.is(fontDb.listFonts().length, 1)
+})
+
+test('should resolve generic font families to their configured fonts', async (t) => {
+  const fontBuffer = await fs.readFile(join(__dirname, '../example/SourceHanSerifCN-Light-subset.ttf'))
+  const pacificoBuffer = await fs.readFile(join(__dirname, './Pacifico-Regular.ttf'))
+  const render = (family: string) => {
+    const svg = `<svg xmlns="http://www.w3.org/2000/svg" width="300" height="100" viewBox="0 0 300 100">
+      <text x="10" y="70" fill="blue" font-family="${family}" font-size="60">Hello</text>
+    </svg>`
+    const resvg = new Resvg(svg, {
+      font: {
+        fontBuffers: [pacificoBuffer, fontBuffer],
+        defaultFontFamily: 'Source Han Serif CN Light',
+        monospaceFamily: 'Pacifico',
+        serifFamily: 'Not Loaded Family',
+      },
+    })
+    return Array.from(resvg.render().pixels)
+  }
+
+  // `monospace` uses its configured family.
+  t.deepEqual(render('monospace'), render('Pacifico'))
+  // `serif` falls back to the default family because its configured family is not loaded.
+  t.deepEqual(render('serif'), render('Source Han Serif CN Light'))
+  t.notDeepEqual(render('monospace'), render('serif'))
+})
+
+test('should render serif and sans-serif with their own configured fonts', async (t) => {
+  const fontBuffer = await fs.readFile(join(__dirname, '../example/SourceHanSerifCN-Light-subset.ttf'))
+  const pacificoBuffer = await fs.readFile(join(__dirname, './Pacifico-Regular.ttf'))
+  const svg = `<svg xmlns="http://www.w3.org/2000/svg" width="300" height="200" viewBox="0 0 300 200">
+    <text id="serif" x="10" y="70" fill="blue" font-family="serif" font-size="60">水 Hello</text>
+    <text id="sans-serif" x="10" y="170" fill="blue" font-family="sans-serif" font-size="60">水 Hello</text>
+  </svg>`
+  const font = {
+    fontBuffers: [pacificoBuffer, fontBuffer],
+    defaultFontFamily: 'Pacifico',
+    serifFamily: 'Source Han Serif CN Light',
+    sansSerifFamily: 'Pacifico',
+  }
+  const resvg = new Resvg(svg, { font })
+
+  // Both families used to resolve to `defaultFontFamily`.
+  const [serif, sansSerif] = resvg.fontReport()
+  t.is(serif.face?.family, 'Source Han Serif CN Light')
+  t.is(sansSerif.face?.family, 'Pacifico')
+
+  const explicit = svg.replace('"serif"', '"Source Han Serif CN Light"').replace('"sans-serif"', '"Pacifico"')
+  t.deepEqual(resvg.render().pixels, new Resvg(explicit, { font }).render().pixels)
+})
+
+test('should be load custom WOFF font', async (t) => {
+  const svg = `
+  <svg xmlns="http://www.w3.org/2000/svg" width="500" height="200" viewBox="0 0 500 200">
+    <text fill="blue" font-size="60">
+      <tspan x="40" y="100">Hello resvg-js</tspan>
+    </text>
+  </svg>
+  `
+  const render = (fontBuffer: Uint8Array) => {
+    const resvg = new Resvg(svg, { font: { fontBuffers: [fontBuffer] } })
+    return Array.from(resvg.render().pixels)
+  }
+  const ttf = await fs.readFile(join(__dirname, './Pacifico-Regular.ttf'))
+  const woff = await fs.readFile(join(__dirname, './Pacifico-Regular.woff'))
+
+  t.deepEqual(render(woff), render(ttf))
+})
+
+test('should take missing glyphs from fallbackFamilies', async (t) => {
+  const svg = `<svg xmlns="http://www.w3.org/2000/svg" width="400" height="120" viewBox="0 0 400 120">
+    <text x="10" y="80" fill="blue" font-family="Source Han Serif CN Light" font-size="60">水 Hello</text>
+  </svg>`
+  const sourceHan = await fs.readFile(join(__dirname, '../example/SourceHanSerifCN-Light-subset.ttf'))
+  const pacifico = await fs.readFile(join(__dirname, './Pacifico-Regular.ttf'))
+  const render = (fontBuffers: Uint8Array[], fallbackFamilies?: string[] | { [script: string]: string[] }) => {
+    const resvg = new Resvg(svg, { font: { fontBuffers, fallbackFamilies } })
+    return Array.from(resvg.render().pixels)
+  }
+  const withoutFallback = render([sourceHan])
+  const withFallback = render([sourceHan, pacifico], ['Pacifico'])
+
+  // The Latin letters are missing from Source Han, they are taken from Pacifico.
+  t.true(
+    withFallback.join(',').match(/0,0,255/g)!.length > (withoutFallback.join(',').match(/0,0,255/g)?.length ?? 0),
+  )
+  t.deepEqual(render([sourceHan, pacifico], { Latin: ['Pacifico'] }), withFallback)
+
+  // The missing characters are rendered as if their span had the fallback family.
+  const explicit = new Resvg(svg.replace('水 Hello', '水 <tspan font-family="Pacifico">Hello</tspan>'), {
+    font: { fontBuffers: [sourceHan, pacifico] },
+  })
+  t.deepEqual(
+    render([sourceHan, pacifico], { Han: ['Source Han Serif CN Light'], Latin: ['Pacifico'] }),
+    Array.from(explicit.render().pixels),
+  )
+})
+
+test('should report the resolved fonts and missing glyphs', async (t) => {
+  const svg = `<svg xmlns="http://www.w3.org/2000/svg" width="400" height="100" viewBox="0 0 400 100">
+    <text id="latin" x="10" y="80" font-family="Pacifico, serif" font-size="40">Hi 水&#x1F600;</text>
+  </svg>`
+  const sourceHan = await fs.readFile(join(__dirname, '../example/SourceHanSerifCN-Light-subset.ttf'))
+  const pacifico = await fs.readFile(join(__dirname, './Pacifico-Regular.ttf'))
+  const resvg = new Resvg(svg, { font: { fontBuffers: [sourceHan, pacifico] } })
+  const [latin] = resvg.fontReport()
+
+  t.is(latin.elementId, 'latin')
+  t.deepEqual(latin.families, ['Pacifico', 'serif'])
+  t.is(latin.face?.family, 'Pacifico')
+  t.is(latin.face?.bufferIndex, 1)
+  t.deepEqual(latin.missingCodePoints, [0x1f600])
+})
+
+test('should list the loaded fonts', async (t) => {
+  const pacifico = await fs.readFile(join(__dirname, './Pacifico-Regular.ttf'))
+  const fonts = listFonts({ fontBuffers: [pacifico] })
+
+  t.is(fonts.length, 1)
+  t.is(fonts[0].families[0].name, 'Pacifico')
+  t.is(fonts[0].postScriptName, 'Pacifico-Regular')
+  t.is(fonts[0].weight, 400)
+  t.is(fonts[0].bufferIndex, 0)
+
+  const fontDb = new FontDatabase({ fontBuffers: [pacifico] })
+  t.deepEqual(fontDb.listFonts(), fonts)
+})
+
+test('should throw because of invalid options, with the path of the invalid value', (t) => {
+  const svg = `<svg width="100" height="100" xmlns="http://www.w3.org/2000/svg"><rect width="100" height="100" fill="red"/></svg>`
+  const error = t.throws(
+    () => {
+      // @ts-expect-error
+      new Resvg(svg, { fitTo: { mode: 'widht', value: 200 } })
+    },
+    { instanceOf: Error },
+  )
+  t.regex(error.message, /^Invalid options at `fitTo[^`]*`: unknown variant `widht`/)
+
+  const unknownField = t.throws(
+    () => {
+      // @ts-expect-error
+      new Resvg(svg, { backgroud: '#fff' })
+    },
+    { instanceOf: Error },
+  )
+  t.regex(unknownField.message, /^Invalid options at `\.`: unknown field `backgroud`/)
+})
+
+test('should throw because of invalid output options', (t) => {
+  const svg = '<svg width="10" height="10" xmlns="http://www.w3.org/2000/svg"></svg>'
+  const resvg = new Resvg(svg)
+  const image = resvg.render()
+
+  const png = t.throws<ResvgError>(() => image.asPng({ compression: 'smallest' }))
+  t.is(png?.code, 'ERR_INVALID_OPTIONS')
+  t.is(png?.path, 'compression')
+  t.is(t.throws<ResvgError>(() => image.getPixels({ format: 'rgb565' }))?.path, 'format')
+  // @ts-expect-error
+  t.is(t.throws<ResvgError>(() => resvg.renderNode('a', { fitto: {} }))?.code, 'ERR_INVALID_OPTIONS')
+  // @ts-expect-error
+  t.is(t.throws<ResvgError>(() => new FontDatabase({ loadSystemFont: false }))?.code, 'ERR_INVALID_OPTIONS')
+})
+
+test('should treat undefined and null options as not set', (t) => {
+  const svg = '<svg width="10" height="10" xmlns="http://www.w3.org/2000/svg"></svg>'
+  const opts: { background?: string } = {}
+  // @ts-expect-error
+  const resvg = new Resvg(svg, { fitTo: undefined, background: opts.background, crop: null })
+  t.is(resvg.render().width, 10)
+  t.true(resvg.render().asPng({ text: undefined, srgb: null }).length > 0)
+})
+
+test('should expose a stable error code and the position of parse errors', (t) => {
+  const malformed = `<svg width="100" height="100" xmlns="http://www.w3.org/2000/svg">
+  <rect width="100" height="100">
+</svg>`
+  const parseError = t.throws<ResvgError>(() => new Resvg(malformed), { instanceOf: Error })
+  t.is(parseError?.code, 'ERR_SVG_PARSE')
+  t.is(parseError?.line, 3)
+  t.is(typeof parseError?.column, 'number')
+
+  const svg = '<svg width="128" height="128" xmlns="http://www.w3.org/2000/svg"></svg>'
+  const zeroSized = t.throws<ResvgError>(() => new Resvg(svg, { fitTo: { mode: 'zoom', value: 0 } }).render())
+  t.is(zeroSized?.code, 'ERR_ZERO_SIZED')
+})
+
+test('should collect the warnings of each instance', (t) => {
+  const svg = `<svg width="100" height="100" xmlns="http://www.w3.org/2000/svg">
+  <rect id="r1" width="-5" height="10"/>
+</svg>`
+  const resvg = new Resvg(svg)
+  t.deepEqual(
+    resvg.warnings.find((w) => w.elementId === 'r1'),
+    { level: 'warn', message: "Rect 'r1' has an invalid 'width' value. Skipped.", elementId: 'r1' },
+  )
+
+  const valid = new Resvg('<svg width="10" height="10" xmlns="http://www.w3.org/2000/svg"></svg>')
+  valid.render()
+  t.false(valid.warnings.some((w) => w.elementId === 'r1'))
+
+  // The features the PDF export skips are reported too.
+  const filtered = new Resvg(`<svg width="100" height="100" xmlns="http://www.w3.org/2000/svg">
+  <filter id="blur"><feGaussianBlur stdDeviation="5"/></filter>
+  <g id="g1" filter="url(#blur)"><rect width="100" height="100"/></g>
+</svg>`)
+  filtered.toPdf()
+  t.true(filtered.warnings.some((w) => w.message.startsWith('PDF export does not support filters')))
+})
+
+test('should take the element id from the warnings that name one', (t) => {
+  const svg = `<svg width="100" height="100" xmlns="http://www.w3.org/2000/svg">
+  <defs>
+    <pattern id="pt" width="0" height="10"/>
+    <mask id="m1" width="0"/>
+    <filter id="f1" width="0"><feFlood/></filter>
+  </defs>
+  <circle id="c1" r="-1"/>
+  <ellipse id="e1" rx="0" ry="0"/>
+  <polyline id="p1" points="0,0"/>
+  <rect width="10" height="10" fill="url(#pt)"/>
+  <rect width="10" height="10" mask="url(#m1)"/>
+  <rect width="10" height="10" filter="url(#f1)"/>
+</svg>`
+  const resvg = new Resvg(svg)
+  const messages = Object.fromEntries(resvg.warnings.map((w) => [w.elementId, w.message]))
+  t.is(messages.c1, "Circle 'c1' has an invalid 'r' value. Skipped.")
+  t.is(messages.e1, "Ellipse 'e1' has an invalid 'rx' value. Skipped.")
+  t.is(messages.p1, "Polyline 'p1' has less than 2 points. Skipped.")
+  t.is(messages.pt, "Pattern 'pt' has an invalid size. Skipped.")
+  t.is(messages.m1, "Mask 'm1' has an invalid size. Skipped.")
+  t.is(messages.f1, "Filter 'f1' has an invalid region. Skipped.")
+})
+
+test.serial('should write the records enabled by logLevel to the console', (t) => {
+  const svg = `<svg width="100" height="100" xmlns="http://www.w3.org/2000/svg">
+  <rect id="w1" width="-5" height="10"/>
+</svg>`
+  const levels = ['error', 'warn', 'info', 'debug'] as const
+  const original = levels.map((level) => console[level])
+  const logged: [string, string][] = []
+  levels.forEach((level) => {
+    console[level] = (message: string) => logged.push([level, message])
+  })
+  try {
+    new Resvg(svg, { logLevel: 'error' })
+    t.false(logged.some(([, message]) => message.includes("'w1'")))
+
+    new Resvg(svg, { logLevel: 'warn' })
+    const warning = logged.find(([, message]) => message.includes("'w1'"))
+    t.is(warning?.[0], 'warn')
+    t.true(warning?.[1].endsWith("Rect 'w1' has an invalid 'width' value. Skipped."))
+    t.false(logged.some(([level]) => level === 'info' || level === 'debug'))
+  } finally {
+    levels.forEach((level, i) => {
+      console[level] = original[i]
+    })
+  }
+})
+
+test('should enforce the resource limits', (t) => {
+  const svg = '<svg width="100" height="100" xmlns="http://www.w3.org/2000/svg"><rect width="100" height="100"/></svg>'
+  const zoom = t.throws<ResvgError>(() =>
+    new Resvg(svg, { fitTo: { mode: 'zoom', value: 1000 }, limits: { maxPixels: 4096 * 4096 } }).render(),
+  )
+  t.is(zoom?.code, 'ERR_LIMIT_PIXELS')
+  const wide = t.throws<ResvgError>(() => new Resvg(svg, { limits: { maxWidth: 50 } }).render())
+  t.is(wide?.code, 'ERR_LIMIT_WIDTH')
+  t.is(new Resvg(svg, { limits: { maxPixels: 100 * 100, maxWidth: 100, maxHeight: 100 } }).render().width, 100)
+
+  // Each group uses the previous one twice, 2^30 rectangles once expanded.
+  let groups = '<g id="g0"><rect width="1" height="1"/></g>'
+  for (let i = 1; i <= 30; i++) {
+    groups += `<g id="g${i}"><use xlink:href="#g${i - 1}"/><use xlink:href="#g${i - 1}"/></g>`
+  }
+  const laughs = `<svg width="100" height="100" xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink">
+  <defs>${groups}</defs>
+  <use xlink:href="#g30"/>
+</svg>`
+  const nodes = t.throws<ResvgError>(() => new Resvg(laughs, { limits: { maxNodes: 10000 } }))
+  t.is(nodes?.code, 'ERR_LIMIT_NODES')
+  const depth = t.throws<ResvgError>(() => new Resvg(laughs, { limits: { maxUseDepth: 5 } }))
+  t.is(depth?.code, 'ERR_LIMIT_USE_DEPTH')
+
+  const filtered = `<svg width="100" height="100" xmlns="http://www.w3.org/2000/svg">
+  <filter id="blur"><feGaussianBlur stdDeviation="5"/></filter>
+  <rect width="100" height="100" filter="url(#blur)"/>
+</svg>`
+  const filterRegion = t.throws<ResvgError>(() => new Resvg(filtered, { limits: { maxFilterRegion: 1000 } }).render())
+  t.is(filterRegion?.code, 'ERR_LIMIT_FILTER_REGION')
+})
+
 test('should throw because invalid SVG (blank string)', (t) => {
   const error = t.throws(
     () => {
@@ -5,13 +5,15 @@ export type ResvgRenderOptions = {
     loadSystemFonts?: boolean // Default: true, if set to false, it will be faster.
     fontFiles?: string[] // A list of local font file paths to load.
     fontDirs?: string[] // A list of local font directories to load.
+    fontBuffers?: Uint8Array[] // A list of raw font buffers to load, WOFF and WOFF2 are decoded as well as font files.
     defaultFontSize?: number // Default: 12
     defaultFontFamily?: string // Default: "", if `loadSystemFonts` is enabled, it will be set to the first font in the list of system fonts.
-    serifFamily?: string
-    sansSerifFamily?: string
-    cursiveFamily?: string
-    fantasyFamily?: string
-    monospaceFamily?: string
+    serifFamily?: string // Default: "Times New Roman", falls back to `defaultFontFamily` when not loaded.
+    sansSerifFamily?: string // Default: "Arial", falls back to `defaultFontFamily` when not loaded.
+    cursiveFamily?: string // Default: "Comic Sans MS", falls back to `defaultFontFamily` when not loaded.
+    fantasyFamily?: string // Default: "Impact", falls back to `defaultFontFamily` when not loaded.
+    monospaceFamily?: string // Default: "Courier New", falls back to `defaultFontFamily` when not loaded.
+    fallbackFamilies?: string[] | { [script: string]: string[] } // Default: [], families to take missing glyphs from in order, optionally keyed by Unicode script name (`Latin`, `Han`...) with a `default` list.
   }
   dpi?: number
   languages?: string[]
@@ -31,6 +33,24 @@ export type ResvgRenderOptions = {
     | { mode: 'width'; value: number }
     | { mode: 'height'; value: number }
     | { mode: 'zoom'; value: number }
+    | {
+        mode: 'box'
+        value: {
+          width: number
+          height: number
+          fit?: 'contain' | 'cover' | 'fill' | 'inside' | 'outside' // Default: 'contain', letterboxed with the `background`.
+          align?: // Default: 'xMidYMid'
+            | 'xMinYMin'
+            | 'xMidYMin'
+            | 'xMaxYMin'
+            | 'xMinYMid'
+            | 'xMidYMid'
+            | 'xMaxYMid'
+            | 'xMinYMax'
+            | 'xMidYMax'
+            | 'xMaxYMax'
+        }
+      }
   background?: string // Support CSS3 color, e.g. rgba(255, 255, 255, .8)
   crop?: {
     left: number
@@ -39,6 +59,124 @@ export type ResvgRenderOptions = {
     bottom?: number
   }
   logLevel?: 'off' | 'error' | 'warn' | 'info' | 'debug' | 'trace'
+  /** Resource limits to render untrusted SVGs, each one is unlimited when unset. There is no parse timeout. */
+  limits?: {
+    maxPixels?: number // The maximum width times height of the rendered image.
+    maxWidth?: number
+    maxHeight?: number
+    maxNodes?: number // The maximum number of elements, counting each `<use>` element as the elements it references.
+    maxUseDepth?: number // The maximum number of nested `<use>` references.
+    maxFilterRegion?: number // The maximum number of pixels of a filter region.
+  }
+}
+export type RenderVariant = {
+  fitTo?: ResvgRenderOptions['fitTo'] // Default: the `fitTo` render option
+  crop?: ResvgRenderOptions['crop'] // Default: the `crop` render option
+  background?: string // Default: the `background` render option
+}
+export type RenderNodeOptions = {
+  fitTo?: ResvgRenderOptions['fitTo'] // Default: the `fitTo` render option. The original size is the node bounding box.
+}
+export type RenderIntoOptions = {
+  stride?: number // Default: the rendered width * 4. The number of bytes per row of the target buffer.
+  x?: number // Default: 0. The position of the render in the target buffer, in pixels.
+  y?: number // Default: 0
+}
+export type PngOptions = {
+  writeDpi?: boolean // Default: false. Write the `dpi` render option to the pHYs chunk.
+  text?: Record<string, string> // Default: {}. Written as tEXt chunks, or iTXt chunks for non Latin-1 values.
+  srgb?: boolean // Default: false. Write an sRGB chunk.
+  compression?: 'fast' | 'default' | 'best' | 'max' // Default: 'default'. 'max' uses zopfli, it is much slower.
+  filter?: 'adaptive' | 'none' | 'sub' | 'up' | 'average' | 'paeth' // Default: 'sub'
+  palette?: boolean // Default: false. Quantize to an 8-bit palette, alpha is written to the tRNS chunk.
+  maxColors?: number // Default: 256, from 2 to 256.
+  dither?: boolean // Default: false. Floyd-Steinberg dithering when the image has more colors than `maxColors`.
+}
+export type PixelsOptions = {
+  premultiplied?: boolean // Default: true, same as `pixels`.
+  format?: 'rgba' | 'bgra' | 'argb' | 'rgb' | 'gray' // Default: 'rgba'. `rgb` and `gray` drop the alpha channel.
+}
+export type WebpOptions = {
+  quality?: number // Default: 80, from 0 to 100. Ignored when `lossless` is enabled.
+  lossless?: boolean // Default: false
+}
+export type JpegOptions = {
+  quality?: number // Default: 90, from 1 to 100.
+  background?: string // Default: the `background` render option, or white. Used to flatten transparent pixels.
+  progressive?: boolean // Default: false
+  chromaSubsampling?: '4:4:4' | '4:2:2' | '4:2:0' // Default: '4:2:0'
+}
+export type AvifOptions = {
+  quality?: number // Default: 80, from 1 to 100.
+  speed?: number // Default: 6, from 1 (slowest, smallest file) to 10 (fastest).
+}
+export type FontReportEntry = {
+  elementId: string // The id of the `<text>` element.
+  text: string
+  families: string[] // The requested `font-family` list.
+  face: {
+    family: string
+    postScriptName: string
+    path: string | null // The font file, `null` for fonts loaded from buffers.
+    bufferIndex: number | null // The index in `fontBuffers`.
+    index: number // The face index in a font collection.
+    weight: number
+    style: 'normal' | 'italic' | 'oblique'
+  } | null // `null` when no font could be resolved.
+  missingCodePoints: number[] // Code points no loaded font has a glyph for.
+}
+export type FontFace = {
+  families: { name: string; language: string }[] // Every localized family name, the `English (United States)` ones first, e.g. `{ name: 'Arial', language: 'English (United States)' }`.
+  postScriptName: string
+  weight: number
+  style: 'normal' | 'italic' | 'oblique'
+  stretch: string // A CSS `font-stretch` keyword, e.g. 'condensed'.
+  monospaced: boolean
+  path: string | null // The font file, `null` for fonts loaded from buffers.
+  bufferIndex: number | null // The index in `fontBuffers`.
+  index: number // The face index in a font collection.
+}
+/** The errors thrown by resvg-js, `code` is stable and can be matched instead of the message. */
+export type ResvgError = Error & {
+  code:
+    | 'ERR_SVG_PARSE'
+    | 'ERR_SVG_INVALID_SIZE'
+    | 'ERR_SVG_NOT_UTF8'
+    | 'ERR_SVG_MALFORMED_GZIP'
+    | 'ERR_SVG_ELEMENTS_LIMIT'
+    | 'ERR_INVALID_VALUE'
+    | 'ERR_INVALID_INPUT'
+    | 'ERR_INVALID_OPTIONS'
+    | 'ERR_ZERO_SIZED'
+    | 'ERR_UNSUPPORTED_IMAGE'
+    | 'ERR_NODE_NOT_FOUND'
+    | 'ERR_INVALID_STRIDE'
+    | 'ERR_TARGET_TOO_SMALL'
+    | 'ERR_FONT_DECODING'
+    | 'ERR_PNG_ENCODING'
+    | 'ERR_WEBP_ENCODING'
+    | 'ERR_JPEG_ENCODING'
+    | 'ERR_JPEG_TOO_LARGE'
+    | 'ERR_AVIF_ENCODING'
+    | 'ERR_UTF8'
+    | 'ERR_IO'
+    | 'ERR_JSON'
+    | 'ERR_LIMIT_PIXELS'
+    | 'ERR_LIMIT_WIDTH'
+    | 'ERR_LIMIT_HEIGHT'
+    | 'ERR_LIMIT_NODES'
+    | 'ERR_LIMIT_USE_DEPTH'
+    | 'ERR_LIMIT_FILTER_REGION'
+    | 'ERR_IMAGE_TOO_LARGE'
+  line?: number // The line of an `ERR_SVG_PARSE` error, starting at 1.
+  column?: number // The column of an `ERR_SVG_PARSE` error, starting at 1.
+  path?: string // The path of the invalid value of an `ERR_INVALID_OPTIONS` error, e.g. `fitTo.mode`.
+  elementId?: string // The id of an `ERR_NODE_NOT_FOUND` error.
+}
+export type ResvgWarning = {
+  level: 'warn' | 'error'
+  message: string
+  elementId: string | null // Best effort: the id is taken from the messages that name an element, e.g. `Rect 'r1' has an invalid 'width' value.`, and is `null` for the others.
 }
 export class BBox {
   x: number
@@ -51,11 +189,47 @@ export function renderAsync(
   svg: string | Buffer,
   options?: ResvgRenderOptions | null,
   signal?: AbortSignal | null,
+  fontDb?: FontDatabase | null,
 ): Promise<RenderedImage>
+/**
+ * Passes the log records enabled by the `logLevel` option to `callback` instead of writing them to stderr,
+ * `null` restores stderr. The callback is called asynchronously, also for the records of `renderAsync()`.
+ * An error thrown by the callback is written to `console.error` instead of being thrown.
+ */
+export function setLogger(
+  callback: ((level: 'error' | 'warn' | 'info' | 'debug' | 'trace', target: string, message: string) => void) | null,
+): void
+/** Lists the font faces loaded with the given font options. */
+export function listFonts(options?: ResvgRenderOptions['font'] | null): FontFace[]
+/**
+ * Fonts loaded once and shared by `Resvg` instances, loading system fonts is the most expensive
+ * part of constructing a `Resvg`.
+ * When a `FontDatabase` is passed, the `font` render options are replaced by the database options.
+ */
+export class FontDatabase {
+  constructor(options?: ResvgRenderOptions['font'] | null)
+  /** Lists the loaded font faces. */
+  listFonts(): FontFace[]
+}
 export class Resvg {
-  constructor(svg: Buffer | string, options?: ResvgRenderOptions | null)
+  /** Throws when the options are invalid, e.g. an unknown field or an unknown `fitTo` mode. */
+  constructor(svg: Buffer | string, options?: ResvgRenderOptions | null, fontDb?: FontDatabase | null)
   toString(): string
   render(): RenderedImage
+  /** Renders the SVG several times, e.g. at 1x, 2x and 3x, from a single parse */
+  renderMany(variants: RenderVariant[]): RenderedImage[]
+  /**
+   * Renders an SVG into an existing Buffer of premultiplied RGBA pixels, without allocating a new image.
+   * The SVG is drawn over the existing content, the `crop` option is not applied.
+   */
+  renderInto(buffer: Buffer, options?: RenderIntoOptions): void
+  /**
+   * Renders a single element, found by its id, cropped to its bounding box.
+   * Note: unused `<symbol>` elements are not kept in the tree, render the `<use>` element instead.
+   */
+  renderNode(id: string, options?: RenderNodeOptions): RenderedImage
+  /** Export the SVG as a vector PDF Buffer */
+  toPdf(): Buffer
   /**
    * Calculate a maximum bounding box of all visible elements in this SVG.
    *
@@ -77,6 +251,12 @@ export class Resvg {
   imagesToResolve(): Array<string>
   resolveImage(href: string, buffer: Buffer): void
 
+  /** Reports the font each text span was resolved to, one entry per text chunk or per span when a chunk mixes fonts, and per run of characters taken from a fallback family. */
+  fontReport(): FontReportEntry[]
+
+  /** The warnings logged while parsing, loading fonts, rendering and exporting to PDF, whatever the `logLevel` is. */
+  get warnings(): ResvgWarning[]
+
   /** Get the SVG width */
   get width(): number
 
@@ -85,10 +265,21 @@ export class Resvg {
 }
 export class RenderedImage {
   /** Write the image data to Buffer */
-  asPng(): Buffer
+  asPng(options?: PngOptions): Buffer
+
+  /** Encode the image data to WebP Buffer */
+  asWebp(options?: WebpOptions): Buffer
+
+  /** Encode the image data to JPEG Buffer */
+  asJpeg(options?: JpegOptions): Buffer
+
+  /** Encode the image data to AVIF Buffer */
+  asAvif(options?: AvifOptions): Buffer
 
   /** Get the RGBA pixels of the image */
   get pixels(): Buffer
+  /** Get the pixels of the image in the given format, optionally unpremultiplied */
+  getPixels(options?: PixelsOptions): Buffer
 
   /** Get the PNG width */
   get width(): number
@@ -1,4 +1,19 @@
-const { render: _render, renderAsync: _renderAsync, Resvg: _Resvg } = require('./js-binding.js')
+const {
+  render: _render,
+  renderAsync: _renderAsync,
+  Resvg: _Resvg,
+  FontDatabase: _FontDatabase,
+  setLogger: _setLogger,
+} = require('./js-binding.js')
+
+// Font buffers can not be deserialized as options, so they are passed separately.
+function splitFontBuffers(options) {
+  if (!options || !options.font || !options.font.fontBuffers) {
+    return [options, undefined]
+  }
+  const { fontBuffers, ...font } = options.font
+  return [{ ...options, font }, fontBuffers]
+}
 
 module.exports.render = function render(svg, options) {
   if (options) {
@@ -7,17 +22,44 @@ module.exports.render = function render(svg, options) {
   return _render(svg)
 }
 
-module.exports.renderAsync = function renderAsync(svg, options, signal) {
+module.exports.renderAsync = function renderAsync(svg, options, signal, fontDb) {
   if (options) {
-    return _renderAsync(svg, JSON.stringify(options), signal)
+    const [serializableOptions, fontBuffers] = splitFontBuffers(options)
+    return _renderAsync(svg, serializableOptions, signal, fontBuffers, fontDb)
   }
-  return _renderAsync(svg, null, signal)
+  return _renderAsync(svg, null, signal, null, fontDb)
 }
 
 module.exports.Resvg = class Resvg extends _Resvg {
-  constructor(svg, options) {
-    super(svg, JSON.stringify(options))
+  constructor(svg, options, fontDb) {
+    const [serializableOptions, fontBuffers] = splitFontBuffers(options)
+    super(svg, serializableOptions, fontBuffers, fontDb)
+  }
+}
+
+module.exports.FontDatabase = class FontDatabase extends _FontDatabase {
+  constructor(options) {
+    const [serializableOptions, fontBuffers] = splitFontBuffers({ font: options })
+    super(serializableOptions.font, fontBuffers)
   }
 }
 
+module.exports.setLogger = function setLogger(callback) {
+  if (!callback) {
+    return _setLogger(null)
+  }
+  // The callback is called asynchronously, an error it throws would be an uncaught exception.
+  return _setLogger((level, target, message) => {
+    try {
+      callback(level, target, message)
+    } catch (e) {
+      console.error('resvg-js: the logger set with setLogger() threw', e)
+    }
+  })
+}
+
+module.exports.listFonts = function listFonts(options) {
+  return new module.exports.FontDatabase(options).listFonts()
+}
+
 // module.exports.Resvg = _Resvg
@@ -3,19 +3,43 @@
 
 /* auto-generated by NAPI-RS */
 
-export function renderAsync(svg: string | Buffer, options?: string | undefined | null, signal?: AbortSignal | undefined | null): Promise<RenderedImage>
+export function renderAsync(svg: string | Buffer, options?: any | undefined | null, signal?: AbortSignal | undefined | null, fontBuffers?: Array<Buffer> | undefined | null, fontDb?: FontDatabase | undefined | null): Promise<RenderedImage>
+/**
+ * Passes the log records to `callback` instead of writing them to stderr, `null` restores stderr.
+ *
+ * The records of `renderAsync()` are passed from the thread pool through a threadsafe function,
+ * so the callback is always called asynchronously.
+ */
+export function setLogger(callback: ((level: string, target: string, message: string) => void) | null): void
 export class BBox {
   x: number
   y: number
   width: number
   height: number
 }
+/**
+ * Fonts loaded once and shared by `Resvg` instances, loading system fonts is the most expensive
+ * part of constructing a `Resvg`.
+ */
+export class FontDatabase {
+  constructor(options?: any | undefined | null, fontBuffers?: Array<Buffer> | undefined | null)
+  /** Lists the loaded font faces */
+  listFonts(): any
+}
 export class Resvg {
-  constructor(svg: string | Buffer, options?: string | undefined | null)
+  constructor(svg: string | Buffer, options?: any | undefined | null, fontBuffers?: Array<Buffer> | undefined | null, fontDb?: FontDatabase | undefined | null)
   /** Renders an SVG in Node.js */
   render(): RenderedImage
+  /** Renders the SVG several times, e.g. at 1x, 2x and 3x, from a single parse */
+  renderMany(variants?: any | undefined | null): Array<RenderedImage>
+  /** Renders a single element, found by its id, cropped to its bounding box */
+  renderNode(id: string, options?: any | undefined | null): RenderedImage
+  /** Renders an SVG into an existing Buffer of premultiplied RGBA pixels */
+  renderInto(buffer: Buffer, options?: any | undefined | null): void
   /** Output usvg-simplified SVG string */
   toString(): string
+  /** Export the SVG as a vector PDF Buffer */
+  toPdf(): Buffer
   /**
    * Calculate a maximum bounding box of all visible elements in this SVG.
    *
@@ -35,6 +59,10 @@ export class Resvg {
   cropByBBox(bbox: BBox): void
   imagesToResolve(): Array<string>
   resolveImage(href: string, buffer: Buffer): void
+  /** Reports the font each text span was resolved to, and the characters no font has */
+  fontReport(): any
+  /** The warnings logged while parsing, loading fonts and rendering */
+  get warnings(): any
   /** Get the SVG width */
   get width(): number
   /** Get the SVG height */
@@ -42,9 +70,17 @@ export class Resvg {
 }
 export class RenderedImage {
   /** Write the image data to Buffer */
-  asPng(): Buffer
+  asPng(options?: any | undefined | null): Buffer
+  /** Encode the image data to WebP Buffer */
+  asWebp(options?: any | undefined | null): Buffer
+  /** Encode the image data to JPEG Buffer */
+  asJpeg(options?: any | undefined | null): Buffer
+  /** Encode the image data to AVIF Buffer */
+  asAvif(options?: any | undefined | null): Buffer
   /** Get the RGBA pixels of the image */
   get pixels(): Buffer
+  /** Get the pixels of the image in the given format, optionally unpremultiplied */
+  getPixels(options?: any | undefined | null): Buffer
   /** Get the PNG width */
   get width(): number
   /** Get the PNG height */
@@ -310,9 +310,11 @@ if (!nativeBinding) {
   throw new Error(`Failed to load native binding`)
 }
 
-const { BBox, Resvg, RenderedImage, renderAsync } = nativeBinding
+const { BBox, FontDatabase, Resvg, RenderedImage, renderAsync, setLogger } = nativeBinding
 
 module.exports.BBox = BBox
+module.exports.FontDatabase = FontDatabase
 module.exports.Resvg = Resvg
 module.exports.RenderedImage = RenderedImage
 module.exports.renderAsync = renderAsync
+module.exports.setLogger = setLogger
@@ -0,0 +1,330 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use crate::error::Error;
+use crate::options::{
+    JsAvifOptions, JsChromaSubsampling, JsJpegOptions, JsPixelFormat, JsPixelsOptions,
+    JsPngCompression, JsPngFilter, JsPngOptions, JsWebpOptions,
+};
+use crate::quantize;
+use jpeg_encoder::{ColorType, SamplingFactor};
+use ravif::{Img, RGBA8};
+use resvg::tiny_skia::Pixmap;
+
+/// Converts the premultiplied pixmap data to straight RGBA.
+///
+/// Most encoders expect unpremultiplied colors, just like `Pixmap::encode_png` does internally.
+fn demultiplied_rgba(pix: &Pixmap) -> Vec<u8> {
+    let mut data = Vec::with_capacity(pix.data().len());
+    for pixel in pix.pixels() {
+        let c = pixel.demultiply();
+        data.extend_from_slice(&[c.red(), c.green(), c.blue(), c.alpha()]);
+    }
+    data
+}
+
+/// Converts the pixmap to raw pixels in the requested channel order.
+pub(crate) fn raw_pixels(pix: &Pixmap, options: &JsPixelsOptions) -> Vec<u8> {
+    let channels = match options.format {
+        JsPixelFormat::Rgba | JsPixelFormat::Bgra | JsPixelFormat::Argb => 4,
+        JsPixelFormat::Rgb => 3,
+        JsPixelFormat::Gray => 1,
+    };
+    let mut data = Vec::with_capacity(pix.pixels().len() * channels);
+    for pixel in pix.pixels() {
+        let [r, g, b, a] = if options.premultiplied {
+            [pixel.red(), pixel.green(), pixel.blue(), pixel.alpha()]
+        } else {
+            let c = pixel.demultiply();
+            [c.red(), c.green(), c.blue(), c.alpha()]
+        };
+        match options.format {
+            JsPixelFormat::Rgba => data.extend_from_slice(&[r, g, b, a]),
+            JsPixelFormat::Bgra => data.extend_from_slice(&[b, g, r, a]),
+            JsPixelFormat::Argb => data.extend_from_slice(&[a, r, g, b]),
+            JsPixelFormat::Rgb => data.extend_from_slice(&[r, g, b]),
+            JsPixelFormat::Gray => {
+                let luma = 0.2126 * f32::from(r) + 0.7152 * f32::from(g) + 0.0722 * f32::from(b);
+                data.push(luma.round() as u8);
+            }
+        }
+    }
+    data
+}
+
+/// Encodes the pixmap to PNG.
+///
+/// `dpi` is the one configured in `JsOptions`, it is only written when `options.write_dpi` is set.
+pub(crate) fn encode_png(pix: &Pixmap, dpi: f32, options: &JsPngOptions) -> Result<Vec<u8>, Error> {
+    let mut data = demultiplied_rgba(pix);
+
+    let mut buffer = vec![];
+    let mut encoder = png::Encoder::new(&mut buffer, pix.width(), pix.height());
+    encoder.set_depth(png::BitDepth::Eight);
+    // The `png` crate writes PLTE and tRNS before the sRGB, gAMA and cHRM chunks, which must come
+    // first, so they are written after the header, along with the image data.
+    let mut palette = None;
+    // Bytes per pixel
+    let bpp = if options.palette {
+        let indexed = quantize::quantize(
+            &data,
+            pix.width() as usize,
+            options.max_colors as usize,
+            options.dither,
+        );
+        encoder.set_color(png::ColorType::Indexed);
+        palette = Some((indexed.rgb_palette(), indexed.trns()));
+        data = indexed.indices;
+        1
+    } else {
+        encoder.set_color(png::ColorType::Rgba);
+        4
+    };
+    encoder.set_compression(match options.compression {
+        JsPngCompression::Fast => png::Compression::Fast,
+        JsPngCompression::Default => png::Compression::Default,
+        JsPngCompression::Best | JsPngCompression::Max => png::Compression::Best,
+    });
+    let filter = match options.filter {
+        JsPngFilter::Adaptive => None,
+        JsPngFilter::None => Some(png::FilterType::NoFilter),
+        JsPngFilter::Sub => Some(png::FilterType::Sub),
+        JsPngFilter::Up => Some(png::FilterType::Up),
+        JsPngFilter::Average => Some(png::FilterType::Avg),
+        JsPngFilter::Paeth => Some(png::FilterType::Paeth),
+    };
+    match filter {
+        Some(filter) => encoder.set_filter(filter),
+        None => encoder.set_adaptive_filter(png::AdaptiveFilterType::Adaptive),
+    }
+    if options.srgb {
+        encoder.set_srgb(png::SrgbRenderingIntent::Perceptual);
+    }
+    for (keyword, text) in &options.text {
+        // tEXt only supports Latin-1, fall back to the UTF-8 iTXt chunk.
+        if text.chars().all(|c| (c as u32) < 0x100) {
+            encoder.add_text_chunk(keyword.clone(), text.clone())?;
+        } else {
+            encoder.add_itxt_chunk(keyword.clone(), text.clone())?;
+        }
+    }
+
+    let mut writer = encoder.write_header()?;
+    if let Some((plte, trns)) = &palette {
+        writer.write_chunk(png::chunk::PLTE, plte)?;
+        if let Some(trns) = trns {
+            writer.write_chunk(png::chunk::tRNS, trns)?;
+        }
+    }
+    if options.write_dpi && dpi.is_finite() && dpi > 0.0 {
+        // pHYs stores pixels per meter.
+        let ppm = (dpi / 0.0254).round() as u32;
+        let mut phys = [0u8; 9];
+        phys[..4].copy_from_slice(&ppm.to_be_bytes());
+        phys[4..8].copy_from_slice(&ppm.to_be_bytes());
+        phys[8] = 1; // The unit is the meter
+        writer.write_chunk(png::chunk::pHYs, &phys)?;
+    }
+    if options.compression == JsPngCompression::Max || palette.is_some() {
+        // The `png` crate can not deflate with zopfli, and refuses indexed image data without a
+        // palette in its header, so filter the scanlines ourselves and write a single IDAT chunk.
+        let filtered = filter_scanlines(&data, pix.width() as usize, bpp, filter);
+        let compressed = match options.compression {
+            JsPngCompression::Max => {
+                let mut compressed = vec![];
+                zopfli::compress(
+                    zopfli::Options::default(),
+                    zopfli::Format::Zlib,
+                    filtered.as_slice(),
+                    &mut compressed,
+                )
+                .map_err(png::EncodingError::from)?;
+                compressed
+            }
+            JsPngCompression::Fast => miniz_oxide::deflate::compress_to_vec_zlib(&filtered, 1),
+            JsPngCompression::Default => miniz_oxide::deflate::compress_to_vec_zlib(&filtered, 6),
+            JsPngCompression::Best => miniz_oxide::deflate::compress_to_vec_zlib(&filtered, 9),
+        };
+        writer.write_chunk(png::chunk::IDAT, &compressed)?;
+    } else {
+        writer.write_image_data(&data)?;
+    }
+    writer.finish()?;
+    Ok(buffer)
+}
+
+/// Prefixes every 8-bit scanline with its filter type byte, as PNG expects it before deflating.
+///
+/// `None` picks the filter per scanline with the minimum sum of absolute differences heuristic,
+/// which is the same heuristic as `png::AdaptiveFilterType::Adaptive`.
+fn filter_scanlines(
+    data: &[u8],
+    width: usize,
+    bpp: usize,
+    filter: Option<png::FilterType>,
+) -> Vec<u8> {
+    const FILTERS: [png::FilterType; 5] = [
+        png::FilterType::NoFilter,
+        png::FilterType::Sub,
+        png::FilterType::Up,
+        png::FilterType::Avg,
+        png::FilterType::Paeth,
+    ];
+
+    let row_len = width * bpp;
+    let mut out = Vec::with_capacity(data.len() + data.len() / row_len.max(1));
+    let zero_row = vec![0u8; row_len];
+    let mut scratch = vec![0u8; row_len];
+    for (y, row) in data.chunks_exact(row_len).enumerate() {
+        let prev = if y == 0 {
+            zero_row.as_slice()
+        } else {
+            &data[(y - 1) * row_len..y * row_len]
+        };
+        let filter = filter.unwrap_or_else(|| {
+            let mut best = (png::FilterType::NoFilter, u64::MAX);
+            for filter in FILTERS {
+                filter_row(filter, bpp, row, prev, &mut scratch);
+                let sum = scratch
+                    .iter()
+                    .map(|&b| (b as i8).unsigned_abs() as u64)
+                    .sum();
+                if sum < best.1 {
+                    best = (filter, sum);
+                }
+            }
+            best.0
+        });
+        filter_row(filter, bpp, row, prev, &mut scratch);
+        out.push(filter as u8);
+        out.extend_from_slice(&scratch);
+    }
+    out
+}
+
+fn filter_row(filter: png::FilterType, bpp: usize, row: &[u8], prev: &[u8], out: &mut [u8]) {
+    for i in 0..row.len() {
+        let a = if i >= bpp { row[i - bpp] } else { 0 };
+        let b = prev[i];
+        let c = if i >= bpp { prev[i - bpp] } else { 0 };
+        let predictor = match filter {
+            png::FilterType::NoFilter => 0,
+            png::FilterType::Sub => a,
+            png::FilterType::Up => b,
+            png::FilterType::Avg => ((a as u16 + b as u16) / 2) as u8,
+            png::FilterType::Paeth => paeth(a, b, c),
+        };
+        out[i] = row[i].wrapping_sub(predictor);
+    }
+}
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = a as i16 + b as i16 - c as i16;
+    let pa = (p - a as i16).abs();
+    let pb = (p - b as i16).abs();
+    let pc = (p - c as i16).abs();
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+/// Encodes the pixmap to WebP.
+pub(crate) fn encode_webp(pix: &Pixmap, options: &JsWebpOptions) -> Result<Vec<u8>, Error> {
+    let data = demultiplied_rgba(pix);
+    crate::webp::encode(
+        &data,
+        pix.width(),
+        pix.height(),
+        options.quality.clamp(0.0, 100.0),
+        options.lossless,
+    )
+    .map_err(Error::WebpEncoding)
+}
+
+/// Flattens the premultiplied pixmap onto an opaque background and returns RGB data.
+///
+/// A translucent background is composited over white first.
+fn flatten_rgb(pix: &Pixmap, background: svgtypes::Color) -> Vec<u8> {
+    let bg_alpha = background.alpha as u32;
+    let blend = |c: u8| (c as u32 * bg_alpha + 255 * (255 - bg_alpha) + 127) / 255;
+    let bg = [
+        blend(background.red),
+        blend(background.green),
+        blend(background.blue),
+    ];
+
+    let mut data = Vec::with_capacity(pix.pixels().len() * 3);
+    for pixel in pix.pixels() {
+        // Premultiplied colors only need the background scaled by the remaining coverage.
+        let rest = 255 - pixel.alpha() as u32;
+        data.extend_from_slice(&[
+            (pixel.red() as u32 + (bg[0] * rest + 127) / 255) as u8,
+            (pixel.green() as u32 + (bg[1] * rest + 127) / 255) as u8,
+            (pixel.blue() as u32 + (bg[2] * rest + 127) / 255) as u8,
+        ]);
+    }
+    data
+}
+
+/// Encodes the pixmap to JPEG.
+///
+/// `options.background` takes precedence over the `background` configured in `JsOptions`.
+pub(crate) fn encode_jpeg(
+    pix: &Pixmap,
+    background: Option<&str>,
+    options: &JsJpegOptions,
+) -> Result<Vec<u8>, Error> {
+    let width = u16::try_from(pix.width()).map_err(|_| Error::JpegTooLarge)?;
+    let height = u16::try_from(pix.height()).map_err(|_| Error::JpegTooLarge)?;
+
+    let background = options
+        .background
+        .as_deref()
+        .or(background)
+        .map(|color| color.parse::<svgtypes::Color>())
+        .transpose()?
+        .unwrap_or_else(svgtypes::Color::white);
+    let data = flatten_rgb(pix, background);
+
+    let mut buffer = vec![];
+    let quality = options.quality.clamp(1.0, 100.0).round() as u8;
+    let mut encoder = jpeg_encoder::Encoder::new(&mut buffer, quality);
+    encoder.set_progressive(options.progressive);
+    encoder.set_sampling_factor(match options.chroma_subsampling {
+        JsChromaSubsampling::Yuv444 => SamplingFactor::R_4_4_4,
+        JsChromaSubsampling::Yuv422 => SamplingFactor::R_4_2_2,
+        JsChromaSubsampling::Yuv420 => SamplingFactor::R_4_2_0,
+    });
+    encoder.encode(&data, width, height, ColorType::Rgb)?;
+    Ok(buffer)
+}
+
+/// Encodes the pixmap to AVIF.
+pub(crate) fn encode_avif(pix: &Pixmap, options: &JsAvifOptions) -> Result<Vec<u8>, Error> {
+    let pixels: Vec<RGBA8> = pix
+        .pixels()
+        .iter()
+        .map(|pixel| {
+            let c = pixel.demultiply();
+            RGBA8::new(c.red(), c.green(), c.blue(), c.alpha())
+        })
+        .collect();
+    let image = Img::new(
+        pixels.as_slice(),
+        pix.width() as usize,
+        pix.height() as usize,
+    );
+
+    let quality = options.quality.clamp(1.0, 100.0);
+    let encoded = ravif::Encoder::new()
+        .with_quality(quality)
+        .with_alpha_quality(quality)
+        .with_speed(options.speed.clamp(1, 10))
+        .encode_rgba(image)?;
+    Ok(encoded.avif_file)
+}
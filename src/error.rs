@@ -3,6 +3,8 @@
 // file, You can obtain one at http://mozilla.org/MPL/2.0/.
 
 use thiserror::Error;
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::JsValue;
 
 #[derive(Error, Debug)]
 pub enum Error {
@@ -14,24 +16,160 @@ pub enum Error {
     Encoding(#[from] png::EncodingError),
     #[error(transparent)]
     Utf8(#[from] std::string::FromUtf8Error),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
     #[error("Target size is zero (please do not set the width/height/zoom options to 0)")]
     ZeroSized,
     #[error("Input must be string or Uint8Array")]
     InvalidInput,
     #[error("Unsupported image types (currently resvg only supports PNG, JPEG and GIF)")]
     UnsupportedImage,
+    #[error("Failed to encode WebP image: {0}")]
+    WebpEncoding(String),
+    #[error(transparent)]
+    JpegEncoding(#[from] jpeg_encoder::EncodingError),
+    #[error("JPEG does not support images wider or taller than 65535 pixels")]
+    JpegTooLarge,
+    #[error(transparent)]
+    AvifEncoding(#[from] ravif::Error),
+    #[error("The stride of the target buffer must be a positive multiple of 4, got {0}")]
+    InvalidStride(u32),
+    #[error("No element with id `{0}` found, note that unused <symbol> elements are not kept")]
+    NodeNotFound(String),
+    #[error("The target buffer is too small for a {0}x{1} render at ({2}, {3})")]
+    TargetTooSmall(u32, u32, u32, u32),
+    #[error("Failed to decode the font: {0}")]
+    FontDecoding(String),
+    #[error("Invalid options at `{0}`: {1}")]
+    InvalidOptions(String, String),
+    #[error("The image is {0}px wide, more than the `limits.maxWidth` of {1}px")]
+    WidthLimit(u32, u32),
+    #[error("The image is {0}px tall, more than the `limits.maxHeight` of {1}px")]
+    HeightLimit(u32, u32),
+    #[error("The image has {0} pixels, more than the `limits.maxPixels` of {1}")]
+    PixelsLimit(u64, u64),
+    #[error(
+        "The SVG has {0} elements once `<use>` is expanded, more than the `limits.maxNodes` of {1}"
+    )]
+    NodesLimit(u64, u64),
+    #[error("The SVG nests `<use>` references deeper than the `limits.maxUseDepth` of {0}")]
+    UseDepthLimit(u32),
+    #[error(
+        "The region of filter `{0}` has {1} pixels, more than the `limits.maxFilterRegion` of {2}"
+    )]
+    FilterRegionLimit(String, u64, u64),
+    #[error("Failed to allocate a {0}x{1} image, it is too large")]
+    ImageTooLarge(u32, u32),
+}
+
+impl Error {
+    /// A stable code for the error, exposed as the `code` property of the thrown JS error.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Error::SVG(_) => "ERR_INVALID_VALUE",
+            Error::USvg(e) => match e {
+                resvg::usvg::Error::NotAnUtf8Str => "ERR_SVG_NOT_UTF8",
+                resvg::usvg::Error::MalformedGZip => "ERR_SVG_MALFORMED_GZIP",
+                resvg::usvg::Error::ElementsLimitReached => "ERR_SVG_ELEMENTS_LIMIT",
+                resvg::usvg::Error::InvalidSize => "ERR_SVG_INVALID_SIZE",
+                resvg::usvg::Error::ParsingFailed(_) => "ERR_SVG_PARSE",
+            },
+            Error::Encoding(_) => "ERR_PNG_ENCODING",
+            Error::Utf8(_) => "ERR_UTF8",
+            Error::Io(_) => "ERR_IO",
+            Error::Json(_) => "ERR_JSON",
+            Error::ZeroSized => "ERR_ZERO_SIZED",
+            Error::InvalidInput => "ERR_INVALID_INPUT",
+            Error::UnsupportedImage => "ERR_UNSUPPORTED_IMAGE",
+            Error::WebpEncoding(_) => "ERR_WEBP_ENCODING",
+            Error::JpegEncoding(_) => "ERR_JPEG_ENCODING",
+            Error::JpegTooLarge => "ERR_JPEG_TOO_LARGE",
+            Error::AvifEncoding(_) => "ERR_AVIF_ENCODING",
+            Error::InvalidStride(_) => "ERR_INVALID_STRIDE",
+            Error::NodeNotFound(_) => "ERR_NODE_NOT_FOUND",
+            Error::TargetTooSmall(..) => "ERR_TARGET_TOO_SMALL",
+            Error::FontDecoding(_) => "ERR_FONT_DECODING",
+            Error::InvalidOptions(..) => "ERR_INVALID_OPTIONS",
+            Error::WidthLimit(..) => "ERR_LIMIT_WIDTH",
+            Error::HeightLimit(..) => "ERR_LIMIT_HEIGHT",
+            Error::PixelsLimit(..) => "ERR_LIMIT_PIXELS",
+            Error::NodesLimit(..) => "ERR_LIMIT_NODES",
+            Error::UseDepthLimit(_) => "ERR_LIMIT_USE_DEPTH",
+            Error::FilterRegionLimit(..) => "ERR_LIMIT_FILTER_REGION",
+            Error::ImageTooLarge(..) => "ERR_IMAGE_TOO_LARGE",
+        }
+    }
+
+    /// The line and column (starting at 1) of an SVG parsing error.
+    pub fn position(&self) -> Option<(u32, u32)> {
+        match self {
+            Error::USvg(resvg::usvg::Error::ParsingFailed(e)) => {
+                let pos = e.pos();
+                Some((pos.row, pos.col))
+            }
+            _ => None,
+        }
+    }
+
+    /// The structured fields set on the thrown JS error, besides `message` and `code`.
+    fn js_fields(&self) -> Vec<(&'static str, JsField)> {
+        let mut fields = Vec::new();
+        if let Some((line, column)) = self.position() {
+            fields.push(("line", JsField::Number(line)));
+            fields.push(("column", JsField::Number(column)));
+        }
+        match self {
+            Error::InvalidOptions(path, _) => fields.push(("path", JsField::String(path.clone()))),
+            Error::NodeNotFound(id) => fields.push(("elementId", JsField::String(id.clone()))),
+            _ => {}
+        }
+        fields
+    }
+}
+
+enum JsField {
+    Number(u32),
+    String(String),
 }
 
 #[cfg(not(target_arch = "wasm32"))]
-impl From<Error> for napi::Error {
-    fn from(e: Error) -> Self {
-        napi::Error::from_reason(format!("{e}"))
+impl Error {
+    /// Converts the error into a JS error with its `code` and structured fields.
+    pub(crate) fn into_napi_error(self, env: napi::Env) -> napi::Error {
+        let error = || -> napi::Result<napi::JsObject> {
+            let mut error = env.create_error(napi::Error::from_reason(format!("{self}")))?;
+            error.set_named_property("code", env.create_string(self.code())?)?;
+            for (name, value) in self.js_fields() {
+                match value {
+                    JsField::Number(n) => error.set_named_property(name, env.create_uint32(n)?)?,
+                    JsField::String(s) => error.set_named_property(name, env.create_string(&s)?)?,
+                }
+            }
+            Ok(error)
+        };
+        match error() {
+            Ok(error) => napi::Error::from(error.into_unknown()),
+            Err(e) => e,
+        }
     }
 }
 
 #[cfg(target_arch = "wasm32")]
 impl From<Error> for js_sys::Error {
     fn from(e: Error) -> Self {
-        js_sys::Error::new(&format!("{}", e))
+        let error = js_sys::Error::new(&format!("{}", e));
+        let set = |name: &str, value: JsValue| {
+            let _ = js_sys::Reflect::set(&error, &JsValue::from_str(name), &value);
+        };
+        set("code", JsValue::from_str(e.code()));
+        for (name, value) in e.js_fields() {
+            match value {
+                JsField::Number(n) => set(name, JsValue::from(n)),
+                JsField::String(s) => set(name, JsValue::from(s)),
+            }
+        }
+        error
     }
 }
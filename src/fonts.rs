@@ -2,56 +2,123 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at http://mozilla.org/MPL/2.0/.
 
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::error::Error;
 use crate::options::*;
-use resvg::usvg::fontdb::{Database, Language};
+use resvg::usvg::fontdb::{
+    Database, FaceInfo, Family, Language, Query, Source, Stretch, Style, Weight, ID,
+};
+use resvg::usvg::{self, FontStretch, FontStyle, NodeKind};
+use serde::Serialize;
+use unicode_script::UnicodeScript;
+use woff2::decode::{convert_woff2_to_ttf, is_woff2};
 
 #[cfg(not(target_arch = "wasm32"))]
 use log::{debug, warn};
 
 #[cfg(not(target_arch = "wasm32"))]
-use resvg::usvg::fontdb::{Family, Query, Source};
+use std::io::Read;
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::Path;
 
 #[cfg(target_arch = "wasm32")]
 use wasm_bindgen::JsCast;
 
-#[cfg(target_arch = "wasm32")]
-use woff2::decode::{convert_woff2_to_ttf, is_woff2};
+/// Where a face loaded from memory comes from.
+#[derive(Clone)]
+pub(crate) enum FontOrigin {
+    /// A WOFF or WOFF2 file, decoded in memory.
+    File(String),
+    /// The index of the buffer in `fontBuffers`.
+    Buffer(usize),
+}
+
+/// A font database, with the origin of the faces loaded from memory, which `Source::Binary` does
+/// not tell.
+#[derive(Clone, Default)]
+pub struct LoadedFonts {
+    pub db: Database,
+    /// The address of the data of each font loaded from memory.
+    origins: Vec<(usize, FontOrigin)>,
+}
+
+impl LoadedFonts {
+    fn load_font_data(&mut self, data: Vec<u8>, origin: FontOrigin) {
+        // Moving the Vec into the Arc keeps the address of its data.
+        self.origins.push((data.as_ptr() as usize, origin));
+        self.db.load_font_source(Source::Binary(Arc::new(data)));
+    }
+
+    fn origin(&self, source: &Source) -> Option<&FontOrigin> {
+        let Source::Binary(data) = source else {
+            return None;
+        };
+        let address = (**data).as_ref().as_ptr() as usize;
+        self.origins
+            .iter()
+            .find(|(a, _)| *a == address)
+            .map(|(_, origin)| origin)
+    }
+
+    /// The file path or the buffer index a face was loaded from.
+    fn face_source(&self, face: &FaceInfo) -> (Option<String>, Option<usize>) {
+        match self.origin(&face.source) {
+            Some(FontOrigin::File(path)) => (Some(path.clone()), None),
+            Some(FontOrigin::Buffer(index)) => (None, Some(*index)),
+            None => match &face.source {
+                #[cfg(not(target_arch = "wasm32"))]
+                Source::File(path) => (Some(path.display().to_string()), None),
+                _ => (None, None),
+            },
+        }
+    }
+}
 
 /// Loads fonts.
 #[cfg(not(target_arch = "wasm32"))]
-pub fn load_fonts(font_options: &JsFontOptions) -> Database {
+pub fn load_fonts(font_options: &JsFontOptions, font_buffers: &[Vec<u8>]) -> LoadedFonts {
     // Create a new font database
-    let mut fontdb = Database::new();
+    let mut fonts = LoadedFonts::default();
     let now = std::time::Instant::now();
 
     // 加载指定路径的字体
     for path in &font_options.font_files {
-        if let Err(e) = fontdb.load_font_file(path) {
+        if let Err(e) = load_font_file(&mut fonts, path) {
             warn!("Failed to load '{}' cause {}.", path, e);
         }
     }
 
     // Load font directories
     for path in &font_options.font_dirs {
-        fontdb.load_fonts_dir(path);
+        load_fonts_dir(&mut fonts, Path::new(path));
+    }
+
+    // 加载字体 buffer
+    for (index, data) in font_buffers.iter().enumerate() {
+        match decode_font_data(data.clone()) {
+            Ok(data) => fonts.load_font_data(data, FontOrigin::Buffer(index)),
+            Err(e) => warn!("Failed to load font buffer {} cause {}.", index, e),
+        }
     }
 
     // 加载系统字体
     // 放到最后加载，这样在获取 default_font_family 时才能优先读取到自定义的字体。
     // https://github.com/RazrFalcon/fontdb/blob/052d74b9eb45f2c4f446846a53f33bd965e2662d/src/lib.rs#L261
     if font_options.load_system_fonts {
-        fontdb.load_system_fonts();
+        fonts.db.load_system_fonts();
     }
 
-    set_font_families(font_options, &mut fontdb);
+    set_font_families(font_options, &mut fonts.db, !font_buffers.is_empty());
 
     debug!(
         "Loaded {} font faces in {}ms.",
-        fontdb.len(),
+        fonts.db.len(),
         now.elapsed().as_micros() as f64 / 1000.0
     );
 
-    fontdb
+    fonts
 }
 
 /// Loads fonts in Wasm.
@@ -59,69 +126,169 @@ pub fn load_fonts(font_options: &JsFontOptions) -> Database {
 pub fn load_wasm_fonts(
     font_options: &JsFontOptions,
     font_buffers: Option<js_sys::Array>,
-    fontdb: &mut Database,
+    fonts: &mut LoadedFonts,
 ) -> Result<(), js_sys::Error> {
     if let Some(ref font_buffers) = font_buffers {
-        for font in font_buffers.values().into_iter() {
+        for (index, font) in font_buffers.values().into_iter().enumerate() {
             let raw_font = font?;
             let font_data = raw_font.dyn_into::<js_sys::Uint8Array>()?.to_vec();
 
-            let font_buffer = if is_woff2(&font_data) {
-                convert_woff2_to_ttf(&mut std::io::Cursor::new(font_data)).unwrap()
-            } else {
-                font_data
-            };
-            fontdb.load_font_data(font_buffer);
+            match decode_font_data(font_data) {
+                Ok(font_buffer) => fonts.load_font_data(font_buffer, FontOrigin::Buffer(index)),
+                Err(e) => log::warn!("Failed to load font buffer {} cause {}.", index, e),
+            }
         }
     }
 
-    set_wasm_font_families(font_options, fontdb, font_buffers);
+    set_wasm_font_families(font_options, &mut fonts.db, font_buffers);
 
     Ok(())
 }
 
+/// Loads the font files of a directory and its subdirectories, like `Database::load_fonts_dir`,
+/// but WOFF and WOFF2 files are loaded too.
 #[cfg(not(target_arch = "wasm32"))]
-fn set_font_families(font_options: &JsFontOptions, fontdb: &mut Database) {
-    let mut default_font_family = font_options.default_font_family.clone().trim().to_string();
-    // Debug: get font lists
-    // for face in fontdb.faces() {
-    //     let family = face
-    //         .families
-    //         .iter()
-    //         .find(|f| f.1 == Language::English_UnitedStates)
-    //         .unwrap_or(&face.families[0]);
-    //     debug!("font_id = {}, family_name = {}", face.id, family.0);
-    // }
-
-    let fontdb_found_default_font_family = fontdb
-        .faces()
-        .find_map(|it| {
-            it.families
+fn load_fonts_dir(fonts: &mut LoadedFonts, dir: &Path) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let is_font = path.extension().and_then(|e| e.to_str()).is_some_and(|e| {
+            ["ttf", "ttc", "otf", "otc", "woff", "woff2"]
                 .iter()
-                .find(|f| f.0 == default_font_family)
-                .map(|f| f.0.clone())
-        })
-        .unwrap_or_default();
+                .any(|font| e.eq_ignore_ascii_case(font))
+        });
+        // `is_file()` and `is_dir()` follow symlinks.
+        if path.is_file() && is_font {
+            if let Err(e) = load_font_file(fonts, &path) {
+                warn!("Failed to load '{}' cause {}.", path.display(), e);
+            }
+        } else if path.is_dir() {
+            load_fonts_dir(fonts, &path);
+        }
+    }
+}
 
+/// Loads a font file, WOFF and WOFF2 files are decoded first.
+#[cfg(not(target_arch = "wasm32"))]
+fn load_font_file(fonts: &mut LoadedFonts, path: impl AsRef<Path>) -> Result<(), Error> {
+    let path = path.as_ref();
+    let mut magic = [0; 4];
+    std::fs::File::open(path)?.read_exact(&mut magic)?;
+    if &magic == b"wOFF" || &magic == b"wOF2" {
+        let data = decode_font_data(std::fs::read(path)?)?;
+        fonts.load_font_data(data, FontOrigin::File(path.display().to_string()));
+    } else {
+        // Keep the file source, so that the font path can be reported.
+        fonts.db.load_font_file(path)?;
+    }
+    Ok(())
+}
+
+/// Decodes WOFF and WOFF2 data to the TrueType/OpenType font they wrap, other data is returned
+/// as is.
+pub(crate) fn decode_font_data(data: Vec<u8>) -> Result<Vec<u8>, Error> {
+    if is_woff2(&data) {
+        convert_woff2_to_ttf(&mut std::io::Cursor::new(data))
+            .map_err(|e| Error::FontDecoding(e.to_string()))
+    } else if data.starts_with(b"wOFF") {
+        decode_woff(&data)
+    } else {
+        Ok(data)
+    }
+}
+
+/// Rebuilds the sfnt of a WOFF 1.0 font, see https://www.w3.org/TR/WOFF/
+fn decode_woff(data: &[u8]) -> Result<Vec<u8>, Error> {
+    let invalid = || Error::FontDecoding("invalid WOFF data".to_string());
+    let read_u16 = |offset: usize| {
+        data.get(offset..offset + 2)
+            .map(|b| u16::from_be_bytes([b[0], b[1]]))
+            .ok_or_else(invalid)
+    };
+    let read_u32 = |offset: usize| {
+        data.get(offset..offset + 4)
+            .map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+            .ok_or_else(invalid)
+    };
+
+    let flavor = read_u32(4)?;
+    let num_tables = read_u16(12)?;
+    // The sfnt table directory header, searchRange is the largest power of two not greater than
+    // numTables, times 16.
+    let entry_selector = 15 - num_tables.max(1).leading_zeros();
+    let search_range = (1u32 << entry_selector) * 16;
+    let range_shift = (num_tables as u32 * 16).saturating_sub(search_range);
+
+    let mut sfnt = Vec::new();
+    sfnt.extend_from_slice(&flavor.to_be_bytes());
+    sfnt.extend_from_slice(&num_tables.to_be_bytes());
+    sfnt.extend_from_slice(&(search_range as u16).to_be_bytes());
+    sfnt.extend_from_slice(&(entry_selector as u16).to_be_bytes());
+    sfnt.extend_from_slice(&(range_shift as u16).to_be_bytes());
+
+    let mut tables = Vec::with_capacity(num_tables as usize);
+    let mut table_offset = 12 + 16 * num_tables as usize;
+    for i in 0..num_tables as usize {
+        let entry = 44 + i * 20;
+        let tag = read_u32(entry)?;
+        let offset = read_u32(entry + 4)? as usize;
+        let comp_length = read_u32(entry + 8)? as usize;
+        let orig_length = read_u32(entry + 12)? as usize;
+        let checksum = read_u32(entry + 16)?;
+
+        let compressed = offset
+            .checked_add(comp_length)
+            .and_then(|end| data.get(offset..end))
+            .ok_or_else(invalid)?;
+        let table = if comp_length < orig_length {
+            // Inflating past origLength would let a small table expand without bound.
+            miniz_oxide::inflate::decompress_to_vec_zlib_with_limit(compressed, orig_length)
+                .map_err(|_| invalid())?
+        } else {
+            compressed.to_vec()
+        };
+        if table.len() != orig_length {
+            return Err(invalid());
+        }
+
+        sfnt.extend_from_slice(&tag.to_be_bytes());
+        sfnt.extend_from_slice(&checksum.to_be_bytes());
+        sfnt.extend_from_slice(&(table_offset as u32).to_be_bytes());
+        sfnt.extend_from_slice(&(orig_length as u32).to_be_bytes());
+        // Tables are 4-byte aligned.
+        table_offset += (orig_length + 3) & !3;
+        tables.push(table);
+    }
+
+    for table in tables {
+        sfnt.extend_from_slice(&table);
+        sfnt.resize((sfnt.len() + 3) & !3, 0);
+    }
+    Ok(sfnt)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn set_font_families(font_options: &JsFontOptions, fontdb: &mut Database, has_font_buffers: bool) {
+    let mut default_font_family = font_options.default_font_family.clone().trim().to_string();
     // 当 default_font_family 为空或系统无该字体时，尝试把 fontdb
     // 中字体列表的第一个字体设置为默认的字体。
-    if default_font_family.is_empty() || fontdb_found_default_font_family.is_empty() {
-        // font_files 或 font_dirs 选项不为空时, 从已加载的字体列表中获取第一个字体的 font family。
-        if !font_options.font_files.is_empty() || !font_options.font_dirs.is_empty() {
+    if default_font_family.is_empty() || !has_font_family(fontdb, &default_font_family) {
+        // font_files、font_dirs 或 font_buffers 不为空时, 从已加载的字体列表中获取第一个字体的 font
+        // family。
+        if !font_options.font_files.is_empty()
+            || !font_options.font_dirs.is_empty()
+            || has_font_buffers
+        {
             default_font_family = get_first_font_family_or_fallback(fontdb);
         }
     }
 
-    fontdb.set_serif_family(&default_font_family);
-    fontdb.set_sans_serif_family(&default_font_family);
-    fontdb.set_cursive_family(&default_font_family);
-    fontdb.set_fantasy_family(&default_font_family);
-    fontdb.set_monospace_family(&default_font_family);
-
     debug!("📝 default_font_family = '{}'", default_font_family);
 
-    #[cfg(not(target_arch = "wasm32"))]
-    find_and_debug_font_path(fontdb, default_font_family.as_str())
+    let default_font_family = find_and_debug_font_path(fontdb, default_font_family);
+    set_generic_families(font_options, fontdb, &default_font_family);
 }
 
 #[cfg(target_arch = "wasm32")]
@@ -132,37 +299,61 @@ fn set_wasm_font_families(
 ) {
     let mut default_font_family = font_options.default_font_family.clone().trim().to_string();
 
-    let fontdb_found_default_font_family = fontdb
-        .faces()
-        .find_map(|it| {
-            it.families
-                .iter()
-                .find(|f| f.0 == default_font_family)
-                .map(|f| f.0.clone())
-        })
-        .unwrap_or_default();
-
     // 当 default_font_family 为空或系统无该字体时，尝试把 fontdb
     // 中字体列表的第一个字体设置为默认的字体。
-    if default_font_family.is_empty() || fontdb_found_default_font_family.is_empty() {
+    if default_font_family.is_empty() || !has_font_family(fontdb, &default_font_family) {
         // font_buffers 选项不为空时, 从已加载的字体列表中获取第一个字体的 font family。
         if let Some(_font_buffers) = font_buffers {
             default_font_family = get_first_font_family_or_fallback(fontdb);
         }
     }
 
-    fontdb.set_serif_family(&default_font_family);
-    fontdb.set_sans_serif_family(&default_font_family);
-    fontdb.set_cursive_family(&default_font_family);
-    fontdb.set_fantasy_family(&default_font_family);
-    fontdb.set_monospace_family(&default_font_family);
+    set_generic_families(font_options, fontdb, &default_font_family);
 }
 
-/// 查询指定 font family 的字体是否存在，如果不存在则使用 fallback_font_family 代替。
+/// Sets each generic family (`serif`, `monospace`, ...) to its configured font family, or to the
+/// default font family when the configured one is not loaded.
+fn set_generic_families(font_options: &JsFontOptions, fontdb: &mut Database, default_family: &str) {
+    let resolve = |fontdb: &Database, generic: &str, family: &str| -> String {
+        let family = family.trim();
+        if !family.is_empty() && has_font_family(fontdb, family) {
+            family.to_string()
+        } else {
+            log::debug!(
+                "📝 {} family '{}' not found, use '{}'",
+                generic,
+                family,
+                default_family
+            );
+            default_family.to_string()
+        }
+    };
+
+    let serif = resolve(fontdb, "serif", &font_options.serif_family);
+    let sans_serif = resolve(fontdb, "sans-serif", &font_options.sans_serif_family);
+    let cursive = resolve(fontdb, "cursive", &font_options.cursive_family);
+    let fantasy = resolve(fontdb, "fantasy", &font_options.fantasy_family);
+    let monospace = resolve(fontdb, "monospace", &font_options.monospace_family);
+
+    fontdb.set_serif_family(serif);
+    fontdb.set_sans_serif_family(sans_serif);
+    fontdb.set_cursive_family(cursive);
+    fontdb.set_fantasy_family(fantasy);
+    fontdb.set_monospace_family(monospace);
+}
+
+/// 查询字体库中是否存在指定的 font family。
+fn has_font_family(fontdb: &Database, font_family: &str) -> bool {
+    fontdb
+        .faces()
+        .any(|face| face.families.iter().any(|f| f.0 == font_family))
+}
+
+/// 查询指定 font family 的字体是否存在，如果不存在则使用字体库中的第一个字体代替。
 #[cfg(not(target_arch = "wasm32"))]
-fn find_and_debug_font_path(fontdb: &mut Database, font_family: &str) {
+fn find_and_debug_font_path(fontdb: &mut Database, font_family: String) -> String {
     let query = Query {
-        families: &[Family::Name(font_family)],
+        families: &[Family::Name(&font_family)],
         ..Query::default()
     };
 
@@ -179,20 +370,16 @@ fn find_and_debug_font_path(fontdb: &mut Database, font_family: &str) {
                     now.elapsed().as_micros() as f64 / 1000.0
                 );
             }
+            font_family
         }
         None => {
             let first_font_family = get_first_font_family_or_fallback(fontdb);
 
-            fontdb.set_serif_family(&first_font_family);
-            fontdb.set_sans_serif_family(&first_font_family);
-            fontdb.set_cursive_family(&first_font_family);
-            fontdb.set_fantasy_family(&first_font_family);
-            fontdb.set_monospace_family(&first_font_family);
-
             warn!(
                 "Warning: The default font-family '{}' not found, set to '{}'.",
                 font_family, first_font_family,
             );
+            first_font_family
         }
     }
 }
@@ -222,3 +409,394 @@ fn get_first_font_family_or_fallback(fontdb: &mut Database) -> String {
 
     default_font_family
 }
+
+/// Queries the face a text span is shaped with, the same way as usvg does.
+pub(crate) fn query_font(font: &usvg::Font, fontdb: &Database) -> Option<ID> {
+    let mut families: Vec<Family> = font
+        .families
+        .iter()
+        .map(|family| match family.as_str() {
+            "serif" => Family::Serif,
+            "sans-serif" => Family::SansSerif,
+            "cursive" => Family::Cursive,
+            "fantasy" => Family::Fantasy,
+            "monospace" => Family::Monospace,
+            _ => Family::Name(family),
+        })
+        .collect();
+    // usvg uses the serif family as the last resort.
+    families.push(Family::Serif);
+
+    let stretch = match font.stretch {
+        FontStretch::UltraCondensed => Stretch::UltraCondensed,
+        FontStretch::ExtraCondensed => Stretch::ExtraCondensed,
+        FontStretch::Condensed => Stretch::Condensed,
+        FontStretch::SemiCondensed => Stretch::SemiCondensed,
+        FontStretch::Normal => Stretch::Normal,
+        FontStretch::SemiExpanded => Stretch::SemiExpanded,
+        FontStretch::Expanded => Stretch::Expanded,
+        FontStretch::ExtraExpanded => Stretch::ExtraExpanded,
+        FontStretch::UltraExpanded => Stretch::UltraExpanded,
+    };
+    let style = match font.style {
+        FontStyle::Normal => Style::Normal,
+        FontStyle::Italic => Style::Italic,
+        FontStyle::Oblique => Style::Oblique,
+    };
+
+    fontdb.query(&Query {
+        families: &families,
+        weight: Weight(font.weight),
+        stretch,
+        style,
+    })
+}
+
+/// Takes the characters missing from the font of their span from the fallback families.
+///
+/// usvg takes a missing glyph from the first face in the database that has it, whatever the
+/// script of the character is. Instead, each run of characters missing from the font of its span
+/// is split into its own span, whose font family is the fallback family resolved for the script
+/// of each character. usvg shapes the whole chunk for every span, so splitting keeps the shaping.
+///
+/// Returns the resulting runs, with the face each one is shaped with, since the text is converted
+/// to paths afterwards.
+pub(crate) fn apply_fallback_families(
+    tree: &mut usvg::Tree,
+    fontdb: &Database,
+    fallback_families: &JsFallbackFamilies,
+) -> Vec<TextSpanInfo> {
+    let mut infos = vec![];
+    let mut resolved = HashMap::new();
+    for node in tree.root.descendants() {
+        if let NodeKind::Text(ref mut text) = *node.borrow_mut() {
+            for chunk in &mut text.chunks {
+                for span in std::mem::take(&mut chunk.spans) {
+                    let families = span.font.families.clone();
+                    let runs = split_fallback_span(
+                        &chunk.text,
+                        span,
+                        fontdb,
+                        fallback_families,
+                        &mut resolved,
+                    );
+                    for (run, face) in runs {
+                        infos.push(TextSpanInfo {
+                            element_id: text.id.clone(),
+                            text: chunk.text[run.start..run.end].to_string(),
+                            families: families.clone(),
+                            face,
+                        });
+                        chunk.spans.push(run);
+                    }
+                }
+            }
+        }
+    }
+    infos
+}
+
+/// Splits the span into runs of characters that share the same fallback family, and returns them
+/// with the face they are shaped with.
+fn split_fallback_span<'a>(
+    text: &str,
+    span: usvg::TextSpan,
+    fontdb: &Database,
+    fallback_families: &'a JsFallbackFamilies,
+    resolved: &mut HashMap<char, Option<&'a str>>,
+) -> Vec<(usvg::TextSpan, Option<ID>)> {
+    let face = query_font(&span.font, fontdb);
+    let missing = match face {
+        Some(id) if !fallback_families.is_empty() => {
+            missing_chars(fontdb, id, &text[span.start..span.end])
+        }
+        _ => vec![],
+    };
+    if missing.is_empty() {
+        return vec![(span, face)];
+    }
+
+    let mut runs = vec![];
+
+    let mut run_start = span.start;
+    let mut run_family = None;
+    for (offset, c) in text[span.start..span.end].char_indices() {
+        let family = if missing.contains(&c) {
+            *resolved
+                .entry(c)
+                .or_insert_with(|| resolve_fallback(fontdb, fallback_families, c))
+        } else {
+            None
+        };
+        let index = span.start + offset;
+        if family != run_family && index > run_start {
+            runs.push(fallback_span(
+                &span, run_start, index, run_family, face, fontdb,
+            ));
+            run_start = index;
+        }
+        run_family = family;
+    }
+    runs.push(fallback_span(
+        &span, run_start, span.end, run_family, face, fontdb,
+    ));
+    runs
+}
+
+/// The first fallback family of the script of the character that has a glyph for it.
+fn resolve_fallback<'a>(
+    fontdb: &Database,
+    fallback_families: &'a JsFallbackFamilies,
+    c: char,
+) -> Option<&'a str> {
+    let script = c.script().full_name();
+    let fallback = fallback_families
+        .families_for(script)
+        .find_map(|family| family_with_char(fontdb, family, c));
+    match fallback {
+        Some(family) => log::debug!(
+            "📝 Fallback to '{}' for U+{:04X} ({})",
+            family,
+            c as u32,
+            script
+        ),
+        None => log::debug!(
+            "📝 No fallback family has a glyph for U+{:04X} ({})",
+            c as u32,
+            script
+        ),
+    }
+    fallback
+}
+
+/// A copy of the span limited to `start..end`, with the fallback family and its face if any, the
+/// `face` of the span otherwise.
+fn fallback_span(
+    span: &usvg::TextSpan,
+    start: usize,
+    end: usize,
+    family: Option<&str>,
+    face: Option<ID>,
+    fontdb: &Database,
+) -> (usvg::TextSpan, Option<ID>) {
+    let mut run = span.clone();
+    run.start = start;
+    run.end = end;
+    match family {
+        Some(family) => {
+            run.font.families = vec![family.to_string()];
+            let face = query_font(&run.font, fontdb);
+            (run, face)
+        }
+        None => (run, face),
+    }
+}
+
+/// Returns the family if one of its faces has a glyph for the character.
+fn family_with_char<'a>(fontdb: &Database, family: &'a str, c: char) -> Option<&'a str> {
+    fontdb
+        .faces()
+        .any(|face| {
+            face.families.iter().any(|f| f.0 == family)
+                && missing_chars(fontdb, face.id, c.encode_utf8(&mut [0; 4])).is_empty()
+        })
+        .then_some(family)
+}
+
+/// Returns the characters of the text that the face has no glyph for, in order and without
+/// duplicates. Control characters are skipped.
+pub(crate) fn missing_chars(fontdb: &Database, id: ID, text: &str) -> Vec<char> {
+    let mut missing: Vec<char> = vec![];
+    for c in text.chars() {
+        if !c.is_control() && !missing.contains(&c) {
+            missing.push(c);
+        }
+    }
+    fontdb.with_face_data(id, |data, index| {
+        if let Ok(face) = ttf_parser::Face::parse(data, index) {
+            missing.retain(|c| face.glyph_index(*c).is_none());
+        }
+    });
+    missing
+}
+
+/// A run of text kept to report how its font was resolved, since the text is converted to paths.
+#[derive(Clone)]
+pub(crate) struct TextSpanInfo {
+    element_id: String,
+    text: String,
+    /// The families of the span, before a fallback family replaced them.
+    families: Vec<String>,
+    /// The face the run is shaped with.
+    face: Option<ID>,
+}
+
+/// How the font of a text span was resolved, returned by `Resvg.fontReport()`.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct FontReportEntry {
+    element_id: String,
+    text: String,
+    /// The families of the `font-family` attribute.
+    families: Vec<String>,
+    face: Option<ResolvedFace>,
+    /// The code points that no loaded face has a glyph for.
+    missing_code_points: Vec<u32>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ResolvedFace {
+    family: String,
+    post_script_name: String,
+    path: Option<String>,
+    buffer_index: Option<usize>,
+    /// The index of the face in a font collection.
+    index: u32,
+    weight: u16,
+    style: &'static str,
+}
+
+/// Reports the face each run of text is shaped with, and the characters no face has.
+pub(crate) fn font_report(fonts: &LoadedFonts, spans: &[TextSpanInfo]) -> Vec<FontReportEntry> {
+    let fontdb = &fonts.db;
+    let entries: Vec<Vec<char>> = spans
+        .iter()
+        .map(|span| match span.face {
+            Some(id) => missing_chars(fontdb, id, &span.text),
+            None => span.text.chars().filter(|c| !c.is_control()).collect(),
+        })
+        .collect();
+
+    // Characters missing from the resolved face may be taken from any other face.
+    let mut unresolved: Vec<char> = vec![];
+    for missing in &entries {
+        for c in missing {
+            if !unresolved.contains(c) {
+                unresolved.push(*c);
+            }
+        }
+    }
+    for face in fontdb.faces() {
+        if unresolved.is_empty() {
+            break;
+        }
+        let text: String = unresolved.iter().collect();
+        unresolved = missing_chars(fontdb, face.id, &text);
+    }
+
+    spans
+        .iter()
+        .zip(entries)
+        .map(|(span, missing)| FontReportEntry {
+            element_id: span.element_id.clone(),
+            text: span.text.clone(),
+            families: span.families.clone(),
+            face: span.face.and_then(|id| resolved_face(fonts, id)),
+            missing_code_points: missing
+                .into_iter()
+                .filter(|c| unresolved.contains(c))
+                .map(|c| c as u32)
+                .collect(),
+        })
+        .collect()
+}
+
+fn resolved_face(fonts: &LoadedFonts, id: ID) -> Option<ResolvedFace> {
+    let face = fonts.db.face(id)?;
+    let family = face
+        .families
+        .iter()
+        .find(|f| f.1 == Language::English_UnitedStates)
+        .or_else(|| face.families.first())
+        .map(|f| f.0.clone())
+        .unwrap_or_default();
+    let (path, buffer_index) = fonts.face_source(face);
+    Some(ResolvedFace {
+        family,
+        post_script_name: face.post_script_name.clone(),
+        path,
+        buffer_index,
+        index: face.index,
+        weight: face.weight.0,
+        style: style_name(face.style),
+    })
+}
+
+/// A loaded face, returned by `listFonts()`.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct FontFace {
+    /// The family names in every language of the font, English first.
+    families: Vec<FontFamilyName>,
+    post_script_name: String,
+    weight: u16,
+    style: &'static str,
+    stretch: &'static str,
+    monospaced: bool,
+    path: Option<String>,
+    buffer_index: Option<usize>,
+    /// The index of the face in a font collection.
+    index: u32,
+}
+
+#[derive(Serialize)]
+pub(crate) struct FontFamilyName {
+    name: String,
+    language: String,
+}
+
+/// Lists the faces of the database, in the order they were loaded.
+pub(crate) fn list_fonts(fonts: &LoadedFonts) -> Vec<FontFace> {
+    fonts
+        .db
+        .faces()
+        .map(|face| {
+            let (path, buffer_index) = fonts.face_source(face);
+            // fontdb keeps the order of the name table, move the English names first.
+            let mut families: Vec<_> = face.families.iter().collect();
+            families.sort_by_key(|(_, language)| *language != Language::English_UnitedStates);
+            FontFace {
+                families: families
+                    .into_iter()
+                    .map(|(name, language)| FontFamilyName {
+                        name: name.clone(),
+                        language: language.to_string(),
+                    })
+                    .collect(),
+                post_script_name: face.post_script_name.clone(),
+                weight: face.weight.0,
+                style: style_name(face.style),
+                stretch: stretch_name(face.stretch),
+                monospaced: face.monospaced,
+                path,
+                buffer_index,
+                index: face.index,
+            }
+        })
+        .collect()
+}
+
+/// The CSS `font-style` keyword.
+fn style_name(style: Style) -> &'static str {
+    match style {
+        Style::Normal => "normal",
+        Style::Italic => "italic",
+        Style::Oblique => "oblique",
+    }
+}
+
+/// The CSS `font-stretch` keyword.
+fn stretch_name(stretch: Stretch) -> &'static str {
+    match stretch {
+        Stretch::UltraCondensed => "ultra-condensed",
+        Stretch::ExtraCondensed => "extra-condensed",
+        Stretch::Condensed => "condensed",
+        Stretch::SemiCondensed => "semi-condensed",
+        Stretch::Normal => "normal",
+        Stretch::SemiExpanded => "semi-expanded",
+        Stretch::Expanded => "expanded",
+        Stretch::ExtraExpanded => "extra-expanded",
+        Stretch::UltraExpanded => "ultra-expanded",
+    }
+}
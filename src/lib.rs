@@ -2,15 +2,22 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at http://mozilla.org/MPL/2.0/.
 
+use std::cell::{Ref, RefCell};
 use std::sync::Arc;
 
 #[cfg(not(target_arch = "wasm32"))]
 use napi::bindgen_prelude::{
-    AbortSignal, AsyncTask, Buffer, Either, Error as NapiError, Task, Undefined,
+    AbortSignal, AsyncTask, Buffer, ClassInstance, Either, Env, Error as NapiError, Task, Undefined,
 };
 #[cfg(not(target_arch = "wasm32"))]
+use napi::{threadsafe_function::ThreadSafeCallContext, JsFunction};
+#[cfg(not(target_arch = "wasm32"))]
 use napi_derive::napi;
-use options::JsOptions;
+use options::{
+    FitToDef, JsAvifOptions, JsCropOptions, JsFontOptions, JsJpegOptions, JsOptions,
+    JsPixelsOptions, JsPngOptions, JsRenderIntoOptions, JsRenderNodeOptions, JsRenderVariant,
+    JsWebpOptions,
+};
 use pathfinder_content::{
     outline::{Contour, Outline},
     stroke::{LineCap, LineJoin, OutlineStrokeToFill, StrokeStyle},
@@ -18,7 +25,7 @@ use pathfinder_content::{
 use pathfinder_geometry::rect::RectF;
 use pathfinder_geometry::vector::Vector2F;
 use resvg::{
-    tiny_skia::{PathSegment, Pixmap, Point},
+    tiny_skia::{Paint, PathSegment, Pixmap, PixmapMut, Point, Rect, Transform},
     usvg::{self, ImageKind, NodeKind, TreeParsing, TreeTextToPath},
 };
 #[cfg(target_arch = "wasm32")]
@@ -27,11 +34,18 @@ use wasm_bindgen::{
     JsCast,
 };
 
+mod encoder;
 mod error;
 mod fonts;
+mod limits;
+mod logger;
 mod options;
+mod pdf;
+mod quantize;
+mod webp;
 
 use error::Error;
+use fonts::{LoadedFonts, TextSpanInfo};
 use usvg::NodeExt;
 
 #[cfg(all(not(target_family = "wasm"), not(debug_assertions),))]
@@ -56,17 +70,40 @@ pub struct BBox {
     pub height: f64,
 }
 
+/// Fonts loaded once and shared by `Resvg` instances, loading system fonts is the most expensive
+/// part of constructing a `Resvg`.
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+#[cfg_attr(not(target_arch = "wasm32"), napi)]
+#[derive(Clone)]
+pub struct FontDatabase {
+    fonts: Arc<LoadedFonts>,
+    /// The options the fonts were loaded with, they replace the `font` render options.
+    font_options: JsFontOptions,
+}
+
 #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
 #[cfg_attr(not(target_arch = "wasm32"), napi)]
 pub struct Resvg {
     tree: usvg::Tree,
     js_options: JsOptions,
+    /// The render tree built from `tree`, reused across renders until `tree` changes.
+    render_tree: RefCell<Option<resvg::Tree>>,
+    /// The fonts the text was converted with.
+    fonts: Arc<LoadedFonts>,
+    /// The text spans of the tree, before they were converted to paths.
+    text_spans: Vec<TextSpanInfo>,
+    /// The warnings logged while parsing, loading fonts, rendering and exporting to PDF.
+    warnings: RefCell<Vec<logger::Warning>>,
 }
 
 #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
 #[cfg_attr(not(target_arch = "wasm32"), napi)]
 pub struct RenderedImage {
     pix: Pixmap,
+    /// The background color of the render options, used to flatten lossy formats without alpha.
+    background: Option<String>,
+    /// The DPI of the render options, optionally written to PNG files.
+    dpi: f32,
 }
 
 #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
@@ -76,8 +113,60 @@ impl RenderedImage {
     #[cfg(not(target_arch = "wasm32"))]
     #[napi]
     /// Write the image data to Buffer
-    pub fn as_png(&self) -> Result<Buffer, NapiError> {
-        let buffer = self.pix.encode_png().map_err(Error::from)?;
+    pub fn as_png(
+        &self,
+        env: Env,
+        options: Option<serde_json::Value>,
+    ) -> Result<Buffer, NapiError> {
+        let options: JsPngOptions =
+            options::from_json_value(options).map_err(|e| e.into_napi_error(env))?;
+        let buffer = encoder::encode_png(&self.pix, self.dpi, &options)
+            .map_err(|e| e.into_napi_error(env))?;
+        Ok(buffer.into())
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[napi]
+    /// Encode the image data to WebP Buffer
+    pub fn as_webp(
+        &self,
+        env: Env,
+        options: Option<serde_json::Value>,
+    ) -> Result<Buffer, NapiError> {
+        let options: JsWebpOptions =
+            options::from_json_value(options).map_err(|e| e.into_napi_error(env))?;
+        let buffer =
+            encoder::encode_webp(&self.pix, &options).map_err(|e| e.into_napi_error(env))?;
+        Ok(buffer.into())
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[napi]
+    /// Encode the image data to JPEG Buffer
+    pub fn as_jpeg(
+        &self,
+        env: Env,
+        options: Option<serde_json::Value>,
+    ) -> Result<Buffer, NapiError> {
+        let options: JsJpegOptions =
+            options::from_json_value(options).map_err(|e| e.into_napi_error(env))?;
+        let buffer = encoder::encode_jpeg(&self.pix, self.background.as_deref(), &options)
+            .map_err(|e| e.into_napi_error(env))?;
+        Ok(buffer.into())
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[napi]
+    /// Encode the image data to AVIF Buffer
+    pub fn as_avif(
+        &self,
+        env: Env,
+        options: Option<serde_json::Value>,
+    ) -> Result<Buffer, NapiError> {
+        let options: JsAvifOptions =
+            options::from_json_value(options).map_err(|e| e.into_napi_error(env))?;
+        let buffer =
+            encoder::encode_avif(&self.pix, &options).map_err(|e| e.into_napi_error(env))?;
         Ok(buffer.into())
     }
 
@@ -99,8 +188,36 @@ impl RenderedImage {
     #[cfg(target_arch = "wasm32")]
     #[wasm_bindgen(js_name = asPng)]
     /// Write the image data to Uint8Array
-    pub fn as_png(&self) -> Result<js_sys::Uint8Array, js_sys::Error> {
-        let buffer = self.pix.encode_png().map_err(Error::from)?;
+    pub fn as_png(&self, options: JsValue) -> Result<js_sys::Uint8Array, js_sys::Error> {
+        let options: JsPngOptions = options::parse_js_options(&options)?;
+        let buffer = encoder::encode_png(&self.pix, self.dpi, &options)?;
+        Ok(buffer.as_slice().into())
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen(js_name = asWebp)]
+    /// Encode the image data to WebP Uint8Array
+    pub fn as_webp(&self, options: JsValue) -> Result<js_sys::Uint8Array, js_sys::Error> {
+        let options: JsWebpOptions = options::parse_js_options(&options)?;
+        let buffer = encoder::encode_webp(&self.pix, &options)?;
+        Ok(buffer.as_slice().into())
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen(js_name = asJpeg)]
+    /// Encode the image data to JPEG Uint8Array
+    pub fn as_jpeg(&self, options: JsValue) -> Result<js_sys::Uint8Array, js_sys::Error> {
+        let options: JsJpegOptions = options::parse_js_options(&options)?;
+        let buffer = encoder::encode_jpeg(&self.pix, self.background.as_deref(), &options)?;
+        Ok(buffer.as_slice().into())
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen(js_name = asAvif)]
+    /// Encode the image data to AVIF Uint8Array
+    pub fn as_avif(&self, options: JsValue) -> Result<js_sys::Uint8Array, js_sys::Error> {
+        let options: JsAvifOptions = options::parse_js_options(&options)?;
+        let buffer = encoder::encode_avif(&self.pix, &options)?;
         Ok(buffer.as_slice().into())
     }
 
@@ -111,6 +228,14 @@ impl RenderedImage {
         self.pix.data().into()
     }
 
+    /// Get the pixels of the image in the given format, optionally unpremultiplied
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen(js_name = getPixels)]
+    pub fn get_pixels(&self, options: JsValue) -> Result<js_sys::Uint8Array, js_sys::Error> {
+        let options: JsPixelsOptions = options::parse_js_options(&options)?;
+        Ok(encoder::raw_pixels(&self.pix, &options).as_slice().into())
+    }
+
     /// Get the RGBA pixels of the image
     #[cfg(not(target_arch = "wasm32"))]
     #[napi(getter)]
@@ -118,6 +243,19 @@ impl RenderedImage {
         self.pix.data().into()
     }
 
+    /// Get the pixels of the image in the given format, optionally unpremultiplied
+    #[cfg(not(target_arch = "wasm32"))]
+    #[napi]
+    pub fn get_pixels(
+        &self,
+        env: Env,
+        options: Option<serde_json::Value>,
+    ) -> Result<Buffer, NapiError> {
+        let options: JsPixelsOptions =
+            options::from_json_value(options).map_err(|e| e.into_napi_error(env))?;
+        Ok(encoder::raw_pixels(&self.pix, &options).into())
+    }
+
     #[cfg(not(target_arch = "wasm32"))]
     #[napi(getter)]
     /// Get the PNG width
@@ -133,41 +271,141 @@ impl RenderedImage {
     }
 }
 
+#[cfg(not(target_arch = "wasm32"))]
+#[napi]
+impl FontDatabase {
+    #[napi(constructor)]
+    pub fn new(
+        env: Env,
+        options: Option<serde_json::Value>,
+        font_buffers: Option<Vec<Buffer>>,
+    ) -> Result<FontDatabase, NapiError> {
+        let font_options: JsFontOptions =
+            options::from_json_value(options).map_err(|e| e.into_napi_error(env))?;
+        let font_buffers = to_font_buffers(font_buffers);
+        Ok(FontDatabase {
+            fonts: Arc::new(fonts::load_fonts(&font_options, &font_buffers)),
+            font_options,
+        })
+    }
+
+    #[napi]
+    /// Lists the loaded font faces
+    pub fn list_fonts(&self, env: Env) -> Result<serde_json::Value, NapiError> {
+        let faces = fonts::list_fonts(&self.fonts);
+        serde_json::to_value(faces).map_err(|e| Error::from(e).into_napi_error(env))
+    }
+}
+
 #[cfg(not(target_arch = "wasm32"))]
 #[napi]
 impl Resvg {
     #[napi(constructor)]
-    pub fn new(svg: Either<String, Buffer>, options: Option<String>) -> Result<Resvg, NapiError> {
-        Resvg::new_inner(&svg, options)
+    pub fn new(
+        env: Env,
+        svg: Either<String, Buffer>,
+        options: Option<serde_json::Value>,
+        font_buffers: Option<Vec<Buffer>>,
+        font_db: Option<ClassInstance<FontDatabase>>,
+    ) -> Result<Resvg, NapiError> {
+        let font_buffers = to_font_buffers(font_buffers);
+        Resvg::new_inner(&svg, options, &font_buffers, font_db.as_deref())
+            .map_err(|e| e.into_napi_error(env))
     }
 
     fn new_inner(
         svg: &Either<String, Buffer>,
-        options: Option<String>,
-    ) -> Result<Resvg, NapiError> {
-        let js_options: JsOptions = options
-            .and_then(|o| serde_json::from_str(o.as_str()).ok())
-            .unwrap_or_default();
-        let _ = env_logger::builder()
-            .filter_level(js_options.log_level)
-            .try_init();
-
-        let (mut opts, fontdb) = js_options.to_usvg_options();
-        options::tweak_usvg_options(&mut opts);
-        // Parse the SVG string into a tree.
-        let mut tree = match svg {
-            Either::A(a) => usvg::Tree::from_str(a.as_str(), &opts),
-            Either::B(b) => usvg::Tree::from_data(b.as_ref(), &opts),
-        }
-        .map_err(|e| napi::Error::from_reason(format!("{e}")))?;
-        tree.convert_text(&fontdb);
-        Ok(Resvg { tree, js_options })
+        options: Option<serde_json::Value>,
+        font_buffers: &[Vec<u8>],
+        font_db: Option<&FontDatabase>,
+    ) -> Result<Resvg, Error> {
+        let mut js_options: JsOptions = options::from_json_value(options)?;
+
+        let (result, warnings) = logger::capture(js_options.log_level, || {
+            let fonts = match font_db {
+                Some(font_db) => {
+                    js_options.font = font_db.font_options.clone();
+                    font_db.fonts.clone()
+                }
+                None => Arc::new(fonts::load_fonts(&js_options.font, font_buffers)),
+            };
+            let mut opts = js_options.to_usvg_options();
+            options::tweak_usvg_options(&mut opts);
+            // Parse the SVG string into a tree.
+            let mut tree = match svg {
+                Either::A(a) => {
+                    limits::check_document(a.as_bytes(), &js_options.limits)?;
+                    usvg::Tree::from_str(a.as_str(), &opts)
+                }
+                Either::B(b) => {
+                    limits::check_document(b.as_ref(), &js_options.limits)?;
+                    usvg::Tree::from_data(b.as_ref(), &opts)
+                }
+            }?;
+            let text_spans = fonts::apply_fallback_families(
+                &mut tree,
+                &fonts.db,
+                &js_options.font.fallback_families,
+            );
+            tree.convert_text(&fonts.db);
+            Ok::<_, Error>((tree, fonts, text_spans))
+        });
+        let (tree, fonts, text_spans) = result?;
+        Ok(Resvg {
+            tree,
+            js_options,
+            render_tree: RefCell::new(None),
+            fonts,
+            text_spans,
+            warnings: RefCell::new(warnings),
+        })
     }
 
     #[napi]
     /// Renders an SVG in Node.js
-    pub fn render(&self) -> Result<RenderedImage, NapiError> {
-        Ok(self.render_inner()?)
+    pub fn render(&self, env: Env) -> Result<RenderedImage, NapiError> {
+        self.render_inner().map_err(|e| e.into_napi_error(env))
+    }
+
+    #[napi]
+    /// Renders the SVG several times, e.g. at 1x, 2x and 3x, from a single parse
+    pub fn render_many(
+        &self,
+        env: Env,
+        variants: Option<serde_json::Value>,
+    ) -> Result<Vec<RenderedImage>, NapiError> {
+        let variants: Vec<JsRenderVariant> =
+            options::from_json_value(variants).map_err(|e| e.into_napi_error(env))?;
+        self.render_many_inner(&variants)
+            .map_err(|e| e.into_napi_error(env))
+    }
+
+    #[napi]
+    /// Renders a single element, found by its id, cropped to its bounding box
+    pub fn render_node(
+        &self,
+        env: Env,
+        id: String,
+        options: Option<serde_json::Value>,
+    ) -> Result<RenderedImage, NapiError> {
+        let options: JsRenderNodeOptions =
+            options::from_json_value(options).map_err(|e| e.into_napi_error(env))?;
+        self.render_node_inner(&id, &options)
+            .map_err(|e| e.into_napi_error(env))
+    }
+
+    #[napi]
+    /// Renders an SVG into an existing Buffer of premultiplied RGBA pixels
+    pub fn render_into(
+        &self,
+        env: Env,
+        mut buffer: Buffer,
+        options: Option<serde_json::Value>,
+    ) -> Result<(), NapiError> {
+        let options: JsRenderIntoOptions =
+            options::from_json_value(options).map_err(|e| e.into_napi_error(env))?;
+        self.render_into_inner(&mut buffer, &options)
+            .map_err(|e| e.into_napi_error(env))
     }
 
     #[napi]
@@ -177,6 +415,12 @@ impl Resvg {
         self.tree.to_string(&usvg::XmlOptions::default())
     }
 
+    #[napi]
+    /// Export the SVG as a vector PDF Buffer
+    pub fn to_pdf(&self) -> Buffer {
+        self.with_warnings(|| pdf::tree_to_pdf(&self.tree)).into()
+    }
+
     #[napi(js_name = innerBBox)]
     /// Calculate a maximum bounding box of all visible elements in this SVG.
     ///
@@ -244,17 +488,32 @@ impl Resvg {
         self.tree.view_box.rect =
             usvg::NonZeroRect::from_xywh(bbox.x as f32, bbox.y as f32, width, height).unwrap();
         self.tree.size = usvg::Size::from_wh(width, height).unwrap();
+        self.render_tree.take();
     }
 
     #[napi]
-    pub fn images_to_resolve(&self) -> Result<Vec<String>, NapiError> {
-        Ok(self.images_to_resolve_inner()?)
+    pub fn images_to_resolve(&self, env: Env) -> Result<Vec<String>, NapiError> {
+        self.images_to_resolve_inner()
+            .map_err(|e| e.into_napi_error(env))
     }
 
     #[napi]
-    pub fn resolve_image(&self, href: String, buffer: Buffer) -> Result<(), NapiError> {
+    pub fn resolve_image(&self, env: Env, href: String, buffer: Buffer) -> Result<(), NapiError> {
         let buffer = buffer.to_vec();
-        Ok(self.resolve_image_inner(href, buffer)?)
+        self.resolve_image_inner(href, buffer)
+            .map_err(|e| e.into_napi_error(env))
+    }
+
+    #[napi]
+    /// Reports the font each text span was resolved to, and the characters no font has
+    pub fn font_report(&self, env: Env) -> Result<serde_json::Value, NapiError> {
+        self.font_report_inner().map_err(|e| e.into_napi_error(env))
+    }
+
+    /// The warnings logged while parsing, loading fonts, rendering and exporting to PDF
+    #[napi(getter)]
+    pub fn warnings(&self, env: Env) -> Result<serde_json::Value, NapiError> {
+        self.warnings_inner().map_err(|e| e.into_napi_error(env))
     }
 
     /// Get the SVG width
@@ -270,35 +529,107 @@ impl Resvg {
     }
 }
 
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen]
+impl FontDatabase {
+    #[wasm_bindgen(constructor)]
+    pub fn new(
+        options: JsValue,
+        font_buffers: Option<js_sys::Array>,
+    ) -> Result<FontDatabase, js_sys::Error> {
+        let font_options: JsFontOptions = options::parse_js_options(&options)?;
+        let mut fonts = LoadedFonts::default();
+        fonts::load_wasm_fonts(&font_options, font_buffers, &mut fonts)?;
+        Ok(FontDatabase {
+            fonts: Arc::new(fonts),
+            font_options,
+        })
+    }
+
+    /// Lists the loaded font faces
+    #[wasm_bindgen(js_name = listFonts)]
+    pub fn list_fonts(&self) -> Result<JsValue, js_sys::Error> {
+        let faces = fonts::list_fonts(&self.fonts);
+        let faces = serde_json::to_string(&faces).map_err(Error::from)?;
+        js_sys::JSON::parse(&faces).map_err(js_sys::Error::from)
+    }
+}
+
 #[cfg(target_arch = "wasm32")]
 #[wasm_bindgen]
 impl Resvg {
     #[wasm_bindgen(constructor)]
     pub fn new(
         svg: IStringOrBuffer,
-        options: Option<String>,
+        options: JsValue,
         custom_font_buffers: Option<js_sys::Array>,
     ) -> Result<Resvg, js_sys::Error> {
-        let js_options: JsOptions = options
-            .and_then(|o| serde_json::from_str(o.as_str()).ok())
-            .unwrap_or_default();
-
-        let (mut opts, mut fontdb) = js_options.to_usvg_options();
-
-        crate::fonts::load_wasm_fonts(&js_options.font, custom_font_buffers, &mut fontdb)?;
-
-        options::tweak_usvg_options(&mut opts);
-        let mut tree = if js_sys::Uint8Array::instanceof(&svg) {
-            let uintarray = js_sys::Uint8Array::unchecked_from_js_ref(&svg);
-            let svg_buffer = uintarray.to_vec();
-            usvg::Tree::from_data(&svg_buffer, &opts).map_err(Error::from)
-        } else if let Some(s) = svg.as_string() {
-            usvg::Tree::from_str(s.as_str(), &opts).map_err(Error::from)
-        } else {
-            Err(Error::InvalidInput)
-        }?;
-        tree.convert_text(&fontdb);
-        Ok(Resvg { tree, js_options })
+        Resvg::new_inner(&svg, &options, custom_font_buffers, None)
+    }
+
+    /// Creates a `Resvg` with the fonts of `font_db`, which replace the `font` options.
+    ///
+    /// `font_db` is borrowed, it can be used for other instances.
+    #[wasm_bindgen(js_name = withFontDatabase)]
+    pub fn with_font_database(
+        svg: IStringOrBuffer,
+        options: JsValue,
+        font_db: &FontDatabase,
+    ) -> Result<Resvg, js_sys::Error> {
+        Resvg::new_inner(&svg, &options, None, Some(font_db))
+    }
+
+    fn new_inner(
+        svg: &IStringOrBuffer,
+        options: &JsValue,
+        custom_font_buffers: Option<js_sys::Array>,
+        font_db: Option<&FontDatabase>,
+    ) -> Result<Resvg, js_sys::Error> {
+        let mut js_options: JsOptions = options::parse_js_options(options)?;
+
+        let (result, warnings) = logger::capture(js_options.log_level, || {
+            let fonts = match font_db {
+                Some(font_db) => {
+                    js_options.font = font_db.font_options.clone();
+                    font_db.fonts.clone()
+                }
+                None => {
+                    let mut fonts = LoadedFonts::default();
+                    fonts::load_wasm_fonts(&js_options.font, custom_font_buffers, &mut fonts)?;
+                    Arc::new(fonts)
+                }
+            };
+
+            let mut opts = js_options.to_usvg_options();
+            options::tweak_usvg_options(&mut opts);
+            let mut tree = if js_sys::Uint8Array::instanceof(svg) {
+                let uintarray = js_sys::Uint8Array::unchecked_from_js_ref(svg);
+                let svg_buffer = uintarray.to_vec();
+                limits::check_document(&svg_buffer, &js_options.limits)?;
+                usvg::Tree::from_data(&svg_buffer, &opts).map_err(Error::from)
+            } else if let Some(s) = svg.as_string() {
+                limits::check_document(s.as_bytes(), &js_options.limits)?;
+                usvg::Tree::from_str(s.as_str(), &opts).map_err(Error::from)
+            } else {
+                Err(Error::InvalidInput)
+            }?;
+            let text_spans = fonts::apply_fallback_families(
+                &mut tree,
+                &fonts.db,
+                &js_options.font.fallback_families,
+            );
+            tree.convert_text(&fonts.db);
+            Ok::<_, js_sys::Error>((tree, fonts, text_spans))
+        });
+        let (tree, fonts, text_spans) = result?;
+        Ok(Resvg {
+            tree,
+            js_options,
+            render_tree: RefCell::new(None),
+            fonts,
+            text_spans,
+            warnings: RefCell::new(warnings),
+        })
     }
 
     /// Get the SVG width
@@ -318,6 +649,32 @@ impl Resvg {
         Ok(self.render_inner()?)
     }
 
+    /// Renders the SVG several times, e.g. at 1x, 2x and 3x, from a single parse
+    #[wasm_bindgen(js_name = renderMany)]
+    pub fn render_many(&self, variants: JsValue) -> Result<js_sys::Array, js_sys::Error> {
+        let variants: Vec<JsRenderVariant> = options::parse_js_options(&variants)?;
+        let images = self.render_many_inner(&variants)?;
+        Ok(images.into_iter().map(JsValue::from).collect())
+    }
+
+    /// Renders a single element, found by its id, cropped to its bounding box
+    #[wasm_bindgen(js_name = renderNode)]
+    pub fn render_node(
+        &self,
+        id: String,
+        options: JsValue,
+    ) -> Result<RenderedImage, js_sys::Error> {
+        let options: JsRenderNodeOptions = options::parse_js_options(&options)?;
+        Ok(self.render_node_inner(&id, &options)?)
+    }
+
+    /// Renders an SVG into an existing Uint8Array of premultiplied RGBA pixels
+    #[wasm_bindgen(js_name = renderInto)]
+    pub fn render_into(&self, buffer: &mut [u8], options: JsValue) -> Result<(), js_sys::Error> {
+        let options: JsRenderIntoOptions = options::parse_js_options(&options)?;
+        Ok(self.render_into_inner(buffer, &options)?)
+    }
+
     /// Output usvg-simplified SVG string
     #[wasm_bindgen(js_name = toString)]
     pub fn to_string(&self) -> String {
@@ -325,6 +682,14 @@ impl Resvg {
         self.tree.to_string(&usvg::XmlOptions::default())
     }
 
+    /// Export the SVG as a vector PDF Uint8Array
+    #[wasm_bindgen(js_name = toPdf)]
+    pub fn to_pdf(&self) -> js_sys::Uint8Array {
+        self.with_warnings(|| pdf::tree_to_pdf(&self.tree))
+            .as_slice()
+            .into()
+    }
+
     /// Calculate a maximum bounding box of all visible elements in this SVG.
     ///
     /// Note: path bounding box are approx values.
@@ -382,6 +747,7 @@ impl Resvg {
         self.tree.view_box.rect =
             usvg::NonZeroRect::from_xywh(bbox.x as f32, bbox.y as f32, width, height).unwrap();
         self.tree.size = usvg::Size::from_wh(width, height).unwrap();
+        self.render_tree.take();
     }
 
     #[wasm_bindgen(js_name = imagesToResolve)]
@@ -400,6 +766,20 @@ impl Resvg {
         let buffer = buffer.to_vec();
         Ok(self.resolve_image_inner(href, buffer)?)
     }
+
+    /// Reports the font each text span was resolved to, and the characters no font has
+    #[wasm_bindgen(js_name = fontReport)]
+    pub fn font_report(&self) -> Result<JsValue, js_sys::Error> {
+        let report = self.font_report_inner()?;
+        js_sys::JSON::parse(&report.to_string()).map_err(js_sys::Error::from)
+    }
+
+    /// The warnings logged while parsing, loading fonts, rendering and exporting to PDF
+    #[wasm_bindgen(getter)]
+    pub fn warnings(&self) -> Result<JsValue, js_sys::Error> {
+        let warnings = self.warnings_inner()?;
+        js_sys::JSON::parse(&warnings.to_string()).map_err(js_sys::Error::from)
+    }
 }
 
 impl Resvg {
@@ -536,24 +916,148 @@ impl Resvg {
     }
 
     fn render_inner(&self) -> Result<RenderedImage, Error> {
-        let (width, height, transform) = self.js_options.fit_to.fit_to(self.tree.size)?;
-        let mut pixmap = self.js_options.create_pixmap(width, height)?;
+        self.render_variant(
+            &self.js_options.fit_to,
+            &self.js_options.crop,
+            self.js_options.background.as_deref(),
+        )
+    }
+
+    fn render_many_inner(&self, variants: &[JsRenderVariant]) -> Result<Vec<RenderedImage>, Error> {
+        variants
+            .iter()
+            .map(|variant| {
+                self.render_variant(
+                    variant.fit_to.as_ref().unwrap_or(&self.js_options.fit_to),
+                    variant.crop.as_ref().unwrap_or(&self.js_options.crop),
+                    variant
+                        .background
+                        .as_deref()
+                        .or(self.js_options.background.as_deref()),
+                )
+            })
+            .collect()
+    }
+
+    fn render_variant(
+        &self,
+        fit_to: &FitToDef,
+        crop: &JsCropOptions,
+        background: Option<&str>,
+    ) -> Result<RenderedImage, Error> {
+        let limits = &self.js_options.limits;
+        let (width, height, transform) = fit_to.fit_to(self.tree.size, limits)?;
+        limits::check_filter_regions(&self.tree.root, transform, width, height, limits)?;
+        let mut pixmap = options::create_pixmap(width, height, background, limits)?;
         // Render the tree
-        let _image = resvg::Tree::from_usvg(&self.tree).render(transform, &mut pixmap.as_mut());
+        self.with_warnings(|| self.render_tree().render(transform, &mut pixmap.as_mut()));
 
         // Crop the SVG
         let crop_rect = resvg::tiny_skia::IntRect::from_ltrb(
-            self.js_options.crop.left,
-            self.js_options.crop.top,
-            self.js_options.crop.right.unwrap_or(width as i32),
-            self.js_options.crop.bottom.unwrap_or(height as i32),
+            crop.left,
+            crop.top,
+            crop.right.unwrap_or(width as i32),
+            crop.bottom.unwrap_or(height as i32),
         );
 
         if let Some(crop_rect) = crop_rect {
             pixmap = pixmap.clone_rect(crop_rect).unwrap_or(pixmap);
         }
 
-        Ok(RenderedImage { pix: pixmap })
+        Ok(RenderedImage {
+            pix: pixmap,
+            background: background.map(String::from),
+            dpi: self.js_options.dpi,
+        })
+    }
+
+    /// Returns the cached render tree, building it on first use.
+    fn render_tree(&self) -> Ref<'_, resvg::Tree> {
+        if self.render_tree.borrow().is_none() {
+            self.render_tree
+                .replace(Some(resvg::Tree::from_usvg(&self.tree)));
+        }
+        Ref::map(self.render_tree.borrow(), |tree| tree.as_ref().unwrap())
+    }
+
+    fn render_node_inner(
+        &self,
+        id: &str,
+        options: &JsRenderNodeOptions,
+    ) -> Result<RenderedImage, Error> {
+        let node = self
+            .tree
+            .node_by_id(id)
+            .ok_or_else(|| Error::NodeNotFound(id.to_string()))?;
+        // `None` when the node has a zero sized bounding box.
+        let tree = self
+            .with_warnings(|| resvg::Tree::from_usvg_node(&node))
+            .ok_or(Error::ZeroSized)?;
+        let fit_to = options.fit_to.as_ref().unwrap_or(&self.js_options.fit_to);
+        let limits = &self.js_options.limits;
+        let (width, height, transform) = fit_to.fit_to(tree.size, limits)?;
+        limits::check_filter_regions(&node, transform, width, height, limits)?;
+        let mut pixmap = self.js_options.create_pixmap(width, height)?;
+        self.with_warnings(|| tree.render(transform, &mut pixmap.as_mut()));
+
+        Ok(RenderedImage {
+            pix: pixmap,
+            background: self.js_options.background.clone(),
+            dpi: self.js_options.dpi,
+        })
+    }
+
+    /// Renders without allocating a pixmap, the buffer is borrowed as a `PixmapMut`.
+    ///
+    /// The SVG is drawn over the existing content, after the background if any. The `crop`
+    /// option is not applied.
+    fn render_into_inner(
+        &self,
+        data: &mut [u8],
+        options: &JsRenderIntoOptions,
+    ) -> Result<(), Error> {
+        let limits = &self.js_options.limits;
+        let (width, height, transform) = self.js_options.fit_to.fit_to(self.tree.size, limits)?;
+        limits::check_filter_regions(&self.tree.root, transform, width, height, limits)?;
+        let stride = options.stride.unwrap_or(width.saturating_mul(4));
+        if stride == 0 || stride % 4 != 0 {
+            return Err(Error::InvalidStride(stride));
+        }
+        let too_small = Error::TargetTooSmall(width, height, options.x, options.y);
+        let rows = (data.len() / stride as usize) as u32;
+        if options.x.saturating_add(width) > stride / 4 || options.y.saturating_add(height) > rows {
+            return Err(too_small);
+        }
+        let data = &mut data[..rows as usize * stride as usize];
+        let mut pixmap = PixmapMut::from_bytes(data, stride / 4, rows).ok_or(too_small)?;
+
+        let (x, y) = (options.x as f32, options.y as f32);
+        if let Some(color) = self.js_options.background_color()? {
+            let mut paint = Paint::default();
+            paint.set_color(color);
+            if let Some(rect) = Rect::from_xywh(x, y, width as f32, height as f32) {
+                pixmap.fill_rect(rect, &paint, Transform::identity(), None);
+            }
+        }
+        let transform = transform.post_translate(x, y);
+        self.with_warnings(|| self.render_tree().render(transform, &mut pixmap));
+        Ok(())
+    }
+
+    /// Runs `f` with the warnings it logs added to `warnings`.
+    fn with_warnings<T>(&self, f: impl FnOnce() -> T) -> T {
+        let (result, warnings) = logger::capture(self.js_options.log_level, f);
+        self.warnings.borrow_mut().extend(warnings);
+        result
+    }
+
+    fn warnings_inner(&self) -> Result<serde_json::Value, Error> {
+        Ok(serde_json::to_value(&*self.warnings.borrow())?)
+    }
+
+    fn font_report_inner(&self) -> Result<serde_json::Value, Error> {
+        let report = fonts::font_report(&self.fonts, &self.text_spans);
+        Ok(serde_json::to_value(report)?)
     }
 
     fn images_to_resolve_inner(&self) -> Result<Vec<String>, Error> {
@@ -571,7 +1075,7 @@ impl Resvg {
 
     fn resolve_image_inner(&self, href: String, buffer: Vec<u8>) -> Result<(), Error> {
         let resolver = usvg::ImageHrefResolver::default_data_resolver();
-        let (options, _) = self.js_options.to_usvg_options();
+        let options = self.js_options.to_usvg_options();
         let mime = MimeType::parse(&buffer)?.mime_type().to_string();
 
         for node in self.tree.root.descendants() {
@@ -583,21 +1087,27 @@ impl Resvg {
                     false
                 };
                 if matched {
-                    let data = (resolver)(&mime, Arc::new(buffer.clone()), &options);
+                    let data = self
+                        .with_warnings(|| (resolver)(&mime, Arc::new(buffer.clone()), &options));
                     if let Some(kind) = data {
                         i.kind = kind;
                     }
                 }
             }
         }
+        self.render_tree.take();
         Ok(())
     }
 }
 
 #[cfg(not(target_arch = "wasm32"))]
 pub struct AsyncRenderer {
-    options: Option<String>,
+    options: Option<serde_json::Value>,
     svg: Either<String, Buffer>,
+    font_buffers: Vec<Vec<u8>>,
+    font_db: Option<FontDatabase>,
+    /// The error of `compute`, turned into a JS error with its code once back on the main thread.
+    error: Option<Error>,
 }
 
 #[cfg(not(target_arch = "wasm32"))]
@@ -607,8 +1117,18 @@ impl Task for AsyncRenderer {
     type JsValue = RenderedImage;
 
     fn compute(&mut self) -> Result<Self::Output, NapiError> {
-        let resvg = Resvg::new_inner(&self.svg, self.options.clone())?;
-        resvg.render()
+        Resvg::new_inner(
+            &self.svg,
+            self.options.clone(),
+            &self.font_buffers,
+            self.font_db.as_ref(),
+        )
+        .and_then(|resvg| resvg.render_inner())
+        .map_err(|e| {
+            let reason = format!("{e}");
+            self.error = Some(e);
+            NapiError::from_reason(reason)
+        })
     }
 
     fn resolve(
@@ -618,21 +1138,78 @@ impl Task for AsyncRenderer {
     ) -> Result<Self::JsValue, NapiError> {
         Ok(result)
     }
+
+    fn reject(&mut self, env: napi::Env, err: NapiError) -> Result<Self::JsValue, NapiError> {
+        match self.error.take() {
+            Some(e) => Err(e.into_napi_error(env)),
+            None => Err(err),
+        }
+    }
 }
 
 #[cfg(not(target_arch = "wasm32"))]
 #[napi]
 pub fn render_async(
     svg: Either<String, Buffer>,
-    options: Option<String>,
+    options: Option<serde_json::Value>,
     signal: Option<AbortSignal>,
+    font_buffers: Option<Vec<Buffer>>,
+    font_db: Option<ClassInstance<FontDatabase>>,
 ) -> AsyncTask<AsyncRenderer> {
+    let renderer = AsyncRenderer {
+        options,
+        svg,
+        font_buffers: to_font_buffers(font_buffers),
+        font_db: font_db.as_deref().cloned(),
+        error: None,
+    };
     match signal {
-        Some(s) => AsyncTask::with_signal(AsyncRenderer { options, svg }, s),
-        None => AsyncTask::new(AsyncRenderer { options, svg }),
+        Some(s) => AsyncTask::with_signal(renderer, s),
+        None => AsyncTask::new(renderer),
     }
 }
 
+/// Passes the log records to `callback` instead of writing them to stderr, `null` restores stderr.
+///
+/// The records of `renderAsync()` are passed from the thread pool through a threadsafe function,
+/// so the callback is always called asynchronously.
+#[cfg(not(target_arch = "wasm32"))]
+#[napi(
+    ts_args_type = "callback: ((level: string, target: string, message: string) => void) | null"
+)]
+pub fn set_logger(env: Env, callback: Option<JsFunction>) -> Result<(), NapiError> {
+    let js_logger = match callback {
+        Some(callback) => {
+            let mut js_logger: logger::JsLogger = callback.create_threadsafe_function(
+                0,
+                |ctx: ThreadSafeCallContext<logger::JsLogRecord>| {
+                    Ok(vec![
+                        ctx.env.create_string(ctx.value.level)?,
+                        ctx.env.create_string(&ctx.value.target)?,
+                        ctx.env.create_string(&ctx.value.message)?,
+                    ])
+                },
+            )?;
+            // Don't keep the process alive for the logger.
+            js_logger.unref(&env)?;
+            Some(js_logger)
+        }
+        None => None,
+    };
+    logger::set_js_logger(js_logger);
+    Ok(())
+}
+
+/// Copies the font buffers, so that they can be used off the main thread.
+#[cfg(not(target_arch = "wasm32"))]
+fn to_font_buffers(font_buffers: Option<Vec<Buffer>>) -> Vec<Vec<u8>> {
+    font_buffers
+        .unwrap_or_default()
+        .into_iter()
+        .map(Vec::from)
+        .collect()
+}
+
 fn points_to_rect(min: Vector2F, max: Vector2F) -> RectF {
     RectF::new(min, max - min)
 }
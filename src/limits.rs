@@ -0,0 +1,174 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+
+use crate::error::Error;
+use crate::options::JsLimits;
+use resvg::tiny_skia::{Rect, Transform};
+use resvg::usvg::{self, NodeExt, NodeKind};
+
+/// Checks the `maxNodes` and `maxUseDepth` limits on the XML document, before usvg expands the
+/// `<use>` elements, which is where a chain of `<use>` elements exhausts the memory.
+pub(crate) fn check_document(data: &[u8], limits: &JsLimits) -> Result<(), Error> {
+    if limits.max_nodes.is_none() && limits.max_use_depth.is_none() {
+        return Ok(());
+    }
+    let data = if data.starts_with(&[0x1f, 0x8b]) {
+        Cow::Owned(usvg::decompress_svgz(data)?)
+    } else {
+        Cow::Borrowed(data)
+    };
+    // Invalid documents are left to usvg, which reports them.
+    let Ok(text) = std::str::from_utf8(&data) else {
+        return Ok(());
+    };
+    let options = roxmltree::ParsingOptions {
+        allow_dtd: true,
+        ..Default::default()
+    };
+    let Ok(doc) = roxmltree::Document::parse_with_options(text, options) else {
+        return Ok(());
+    };
+
+    let mut measure = Measure {
+        ids: doc
+            .descendants()
+            .filter_map(|node| node.attribute("id").map(|id| (id, node)))
+            .collect(),
+        sizes: HashMap::new(),
+        visiting: HashSet::new(),
+    };
+    let (nodes, use_depth) = measure.measure(doc.root_element());
+    if let Some(max_use_depth) = limits.max_use_depth.filter(|max| use_depth > *max) {
+        return Err(Error::UseDepthLimit(max_use_depth));
+    }
+    if let Some(max_nodes) = limits.max_nodes.filter(|max| nodes > *max) {
+        return Err(Error::NodesLimit(nodes, max_nodes));
+    }
+    Ok(())
+}
+
+/// Measures the elements with their `<use>` references expanded, each element is measured once
+/// so that exponential `<use>` chains are measured in linear time.
+struct Measure<'a, 'input> {
+    ids: HashMap<&'a str, roxmltree::Node<'a, 'input>>,
+    /// The number of elements and the `<use>` depth of each measured element.
+    sizes: HashMap<roxmltree::NodeId, (u64, u32)>,
+    visiting: HashSet<roxmltree::NodeId>,
+}
+
+impl<'a, 'input> Measure<'a, 'input> {
+    /// Measures `root` in post-order with an explicit stack rather than recursion, so that a long
+    /// chain of `<use>` elements can't overflow the stack.
+    fn measure(&mut self, root: roxmltree::Node<'a, 'input>) -> (u64, u32) {
+        let mut stack = vec![(root, false)];
+        while let Some((node, expanded)) = stack.pop() {
+            if self.sizes.contains_key(&node.id()) {
+                continue;
+            }
+            if !expanded {
+                // A recursive `<use>` element, usvg skips it.
+                if !self.visiting.insert(node.id()) {
+                    continue;
+                }
+                stack.push((node, true));
+                let dependencies: Vec<_> = element_children(node)
+                    .chain(self.use_target(node))
+                    .collect();
+                // Reversed, so that the elements are measured in document order.
+                stack.extend(dependencies.into_iter().rev().map(|node| (node, false)));
+                continue;
+            }
+            let mut nodes = 1u64;
+            let mut use_depth = 0;
+            for child in element_children(node) {
+                let (child_nodes, child_depth) = self.size(child);
+                nodes = nodes.saturating_add(child_nodes);
+                use_depth = use_depth.max(child_depth);
+            }
+            if let Some(target) = self.use_target(node) {
+                let (target_nodes, target_depth) = self.size(target);
+                nodes = nodes.saturating_add(target_nodes);
+                use_depth = use_depth.max(target_depth.saturating_add(1));
+            }
+            self.visiting.remove(&node.id());
+            self.sizes.insert(node.id(), (nodes, use_depth));
+        }
+        self.size(root)
+    }
+
+    /// The element referenced by a `<use>` element.
+    fn use_target(&self, node: roxmltree::Node<'a, 'input>) -> Option<roxmltree::Node<'a, 'input>> {
+        if node.tag_name().name() != "use" {
+            return None;
+        }
+        href(node).and_then(|id| self.ids.get(id).copied())
+    }
+
+    /// The measured size of `node`, a node that is still being measured is a recursive `<use>`
+    /// reference and counts as empty.
+    fn size(&self, node: roxmltree::Node<'a, 'input>) -> (u64, u32) {
+        self.sizes.get(&node.id()).copied().unwrap_or((0, 0))
+    }
+}
+
+fn element_children<'a, 'input>(
+    node: roxmltree::Node<'a, 'input>,
+) -> impl Iterator<Item = roxmltree::Node<'a, 'input>> {
+    node.children().filter(|child| child.is_element())
+}
+
+/// The id referenced by a `<use>` element.
+fn href<'a>(node: roxmltree::Node<'a, '_>) -> Option<&'a str> {
+    node.attribute(("http://www.w3.org/1999/xlink", "href"))
+        .or_else(|| node.attribute("href"))?
+        .strip_prefix('#')
+}
+
+/// Checks the `maxFilterRegion` limit on the filters under `root`, rendered with `transform` on a
+/// `width`x`height` image. The regions are clipped to the image, like resvg does.
+pub(crate) fn check_filter_regions(
+    root: &usvg::Node,
+    transform: Transform,
+    width: u32,
+    height: u32,
+    limits: &JsLimits,
+) -> Result<(), Error> {
+    let Some(max_filter_region) = limits.max_filter_region else {
+        return Ok(());
+    };
+    let Some(canvas) = Rect::from_xywh(0.0, 0.0, width as f32, height as f32) else {
+        return Ok(());
+    };
+    for node in root.descendants() {
+        let NodeKind::Group(ref group) = *node.borrow() else {
+            continue;
+        };
+        for filter in &group.filters {
+            let region = match filter.units {
+                usvg::Units::ObjectBoundingBox => node
+                    .calculate_bbox()
+                    .and_then(|bbox| bbox.to_non_zero_rect())
+                    .map(|bbox| filter.rect.bbox_transform(bbox)),
+                usvg::Units::UserSpaceOnUse => filter.rect.transform(node.abs_transform()),
+            };
+            let pixels = region
+                .and_then(|region| region.transform(transform))
+                .and_then(|region| region.to_rect().intersect(&canvas))
+                .map_or(0, |region| {
+                    region.width().ceil() as u64 * region.height().ceil() as u64
+                });
+            if pixels > max_filter_region {
+                return Err(Error::FilterRegionLimit(
+                    filter.id.clone(),
+                    pixels,
+                    max_filter_region,
+                ));
+            }
+        }
+    }
+    Ok(())
+}
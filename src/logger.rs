@@ -0,0 +1,239 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::cell::RefCell;
+#[cfg(not(target_arch = "wasm32"))]
+use std::sync::Mutex;
+use std::sync::Once;
+
+use log::{Level, LevelFilter, Log, Metadata, Record};
+#[cfg(not(target_arch = "wasm32"))]
+use napi::threadsafe_function::{ErrorStrategy, ThreadsafeFunction, ThreadsafeFunctionCallMode};
+use serde::Serialize;
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::prelude::wasm_bindgen;
+
+/// A warning or error logged while parsing, loading fonts or rendering.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Warning {
+    /// `warn` or `error`, like the `logLevel` option.
+    pub level: String,
+    pub message: String,
+    /// The id of the element the message is about, when usvg names it.
+    pub element_id: Option<String>,
+}
+
+/// The records of the current thread, collected for a `Resvg` instance.
+struct Capture {
+    /// The `logLevel` of the instance, the records it enables are also written to stderr.
+    level: LevelFilter,
+    warnings: Vec<Warning>,
+}
+
+thread_local! {
+    static CAPTURE: RefCell<Option<Capture>> = RefCell::new(None);
+}
+
+/// The level used outside of a `Resvg` instance, e.g. when a `FontDatabase` is loaded.
+const DEFAULT_LEVEL: LevelFilter = LevelFilter::Error;
+
+/// A record passed to the JS logger, as `(level, target, message)`.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) struct JsLogRecord {
+    pub level: &'static str,
+    pub target: String,
+    pub message: String,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) type JsLogger = ThreadsafeFunction<JsLogRecord, ErrorStrategy::Fatal>;
+
+/// The function set with `setLogger()`, the records are written to stderr when unset.
+#[cfg(not(target_arch = "wasm32"))]
+static JS_LOGGER: Mutex<Option<JsLogger>> = Mutex::new(None);
+
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen]
+extern "C" {
+    #[wasm_bindgen(js_namespace = console, js_name = error)]
+    fn console_error(message: &str);
+    #[wasm_bindgen(js_namespace = console, js_name = warn)]
+    fn console_warn(message: &str);
+    #[wasm_bindgen(js_namespace = console, js_name = info)]
+    fn console_info(message: &str);
+    #[wasm_bindgen(js_namespace = console, js_name = debug)]
+    fn console_debug(message: &str);
+}
+
+/// The global logger, it dispatches each record to the instance running on the current thread,
+/// so that instances with different options don't share their warnings or log level.
+struct Logger {
+    #[cfg(not(target_arch = "wasm32"))]
+    stderr: env_logger::Logger,
+    /// The filters set with `RUST_LOG`, the records they enable are written whatever the
+    /// `logLevel` is.
+    #[cfg(not(target_arch = "wasm32"))]
+    env_filter: Option<env_logger::Logger>,
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= Level::Warn
+            || metadata.level() <= current_level()
+            || self.env_enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let level = CAPTURE.with(|capture| match capture.borrow_mut().as_mut() {
+            Some(capture) => {
+                if record.level() <= Level::Warn {
+                    capture.warnings.push(Warning::new(record));
+                }
+                capture.level
+            }
+            None => DEFAULT_LEVEL,
+        });
+        if record.level() <= level || self.env_enabled(record.metadata()) {
+            self.write(record);
+        }
+    }
+
+    fn flush(&self) {
+        #[cfg(not(target_arch = "wasm32"))]
+        self.stderr.flush();
+    }
+}
+
+impl Logger {
+    #[cfg(not(target_arch = "wasm32"))]
+    fn env_enabled(&self, metadata: &Metadata) -> bool {
+        self.env_filter
+            .as_ref()
+            .is_some_and(|env_filter| env_filter.enabled(metadata))
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn env_enabled(&self, _metadata: &Metadata) -> bool {
+        false
+    }
+
+    /// Passes the record to the JS logger if one is set, or writes it to stderr.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn write(&self, record: &Record) {
+        let js_logger = JS_LOGGER.lock().unwrap_or_else(|e| e.into_inner());
+        match js_logger.as_ref() {
+            Some(js_logger) => {
+                let record = JsLogRecord {
+                    level: level_name(record.level()),
+                    target: record.target().to_string(),
+                    message: record.args().to_string(),
+                };
+                js_logger.call(record, ThreadsafeFunctionCallMode::NonBlocking);
+            }
+            None => self.stderr.log(record),
+        }
+    }
+
+    /// Writes the record to the browser or Node.js console.
+    #[cfg(target_arch = "wasm32")]
+    fn write(&self, record: &Record) {
+        let message = format!("[{}] {}", record.target(), record.args());
+        match record.level() {
+            Level::Error => console_error(&message),
+            Level::Warn => console_warn(&message),
+            Level::Info => console_info(&message),
+            Level::Debug | Level::Trace => console_debug(&message),
+        }
+    }
+}
+
+/// The name of the level, like the `logLevel` option.
+fn level_name(level: Level) -> &'static str {
+    match level {
+        Level::Error => "error",
+        Level::Warn => "warn",
+        Level::Info => "info",
+        Level::Debug => "debug",
+        Level::Trace => "trace",
+    }
+}
+
+impl Warning {
+    fn new(record: &Record) -> Warning {
+        let message = record.args().to_string();
+        Warning {
+            level: level_name(record.level()).to_string(),
+            element_id: element_id(&message),
+            message,
+        }
+    }
+}
+
+/// The elements usvg names in its messages, e.g. "Rect 'r1' has an invalid 'width' value."
+const ELEMENT_KINDS: &[&str] = &[
+    "Circle", "Ellipse", "Filter", "Gradient", "Mask", "Pattern", "Polygon", "Polyline", "Rect",
+];
+
+fn element_id(message: &str) -> Option<String> {
+    let (kind, rest) = message.split_once(' ')?;
+    if !ELEMENT_KINDS.contains(&kind) {
+        return None;
+    }
+    let id = rest.strip_prefix('\'')?.split('\'').next()?;
+    (!id.is_empty()).then(|| id.to_string())
+}
+
+fn current_level() -> LevelFilter {
+    CAPTURE.with(|capture| {
+        capture
+            .borrow()
+            .as_ref()
+            .map_or(DEFAULT_LEVEL, |capture| capture.level)
+    })
+}
+
+/// Installs the global logger, once.
+pub(crate) fn init() {
+    static INIT: Once = Once::new();
+    INIT.call_once(|| {
+        let logger = Logger {
+            #[cfg(not(target_arch = "wasm32"))]
+            stderr: env_logger::Builder::from_default_env()
+                .filter_level(LevelFilter::Trace)
+                .build(),
+            #[cfg(not(target_arch = "wasm32"))]
+            env_filter: std::env::var_os("RUST_LOG")
+                .map(|_| env_logger::Builder::from_default_env().build()),
+        };
+        if log::set_logger(Box::leak(Box::new(logger))).is_ok() {
+            log::set_max_level(LevelFilter::Trace);
+        }
+    });
+}
+
+/// Runs `f` with the warnings logged on the current thread collected, the records enabled by
+/// `level` are also written to stderr.
+pub(crate) fn capture<T>(level: LevelFilter, f: impl FnOnce() -> T) -> (T, Vec<Warning>) {
+    init();
+    let previous = CAPTURE.with(|capture| {
+        capture.replace(Some(Capture {
+            level,
+            warnings: Vec::new(),
+        }))
+    });
+    let result = f();
+    let capture = CAPTURE.with(|capture| capture.replace(previous));
+    (result, capture.map(|c| c.warnings).unwrap_or_default())
+}
+
+/// Replaces the JS logger, `None` writes the records to stderr again.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn set_js_logger(js_logger: Option<JsLogger>) {
+    init();
+    *JS_LOGGER.lock().unwrap_or_else(|e| e.into_inner()) = js_logger;
+}
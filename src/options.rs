@@ -2,15 +2,17 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at http://mozilla.org/MPL/2.0/.
 
+use std::collections::BTreeMap;
 use std::sync::Arc;
 
 use crate::error::Error;
 #[cfg(not(target_arch = "wasm32"))]
 use napi::{bindgen_prelude::Buffer, Either};
-use resvg::tiny_skia::{Pixmap, Transform};
-use resvg::usvg::fontdb::Database;
+use resvg::tiny_skia::{Color, Pixmap, Transform};
 use resvg::usvg::{self, ImageHrefResolver, ImageKind, Options, TreeParsing};
-use serde::{Deserialize, Deserializer};
+use serde::{de::DeserializeOwned, Deserialize, Deserializer};
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::JsValue;
 
 /// Image fit options.
 /// This provides the deserializer for `usvg::FitTo`.
@@ -30,10 +32,99 @@ pub enum FitToDef {
     Height(u32),
     /// Zoom by factor.
     Zoom(f32),
+    /// Fit into a width and height box, like the CSS `object-fit` property.
+    Box(JsFitBox),
+}
+
+/// The value of the `box` fit mode.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct JsFitBox {
+    pub width: u32,
+    pub height: u32,
+
+    /// Default: contain
+    #[serde(default)]
+    pub fit: JsFit,
+
+    /// Where the image is placed in the box, uses the `preserveAspectRatio` alignment values.
+    ///
+    /// Default: xMidYMid
+    #[serde(default)]
+    pub align: JsAlign,
+}
+
+/// How an image is resized to the `box` fit mode.
+#[derive(Deserialize, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum JsFit {
+    /// Keep the aspect ratio, letterboxed with the `background` to fill the box.
+    #[default]
+    Contain,
+    /// Keep the aspect ratio, cropped to fill the box.
+    Cover,
+    /// Stretch to the box.
+    Fill,
+    /// Keep the aspect ratio, as large as possible while being smaller than the box.
+    Inside,
+    /// Keep the aspect ratio, as small as possible while being larger than the box.
+    Outside,
+}
+
+/// The alignment of the `box` fit mode.
+#[derive(Deserialize, Clone, Copy, Default)]
+pub enum JsAlign {
+    #[serde(rename = "xMinYMin")]
+    XMinYMin,
+    #[serde(rename = "xMidYMin")]
+    XMidYMin,
+    #[serde(rename = "xMaxYMin")]
+    XMaxYMin,
+    #[serde(rename = "xMinYMid")]
+    XMinYMid,
+    #[default]
+    #[serde(rename = "xMidYMid")]
+    XMidYMid,
+    #[serde(rename = "xMaxYMid")]
+    XMaxYMid,
+    #[serde(rename = "xMinYMax")]
+    XMinYMax,
+    #[serde(rename = "xMidYMax")]
+    XMidYMax,
+    #[serde(rename = "xMaxYMax")]
+    XMaxYMax,
+}
+
+impl JsAlign {
+    /// The fraction of the free space placed before the image, horizontally and vertically.
+    fn factors(self) -> (f32, f32) {
+        match self {
+            JsAlign::XMinYMin => (0.0, 0.0),
+            JsAlign::XMidYMin => (0.5, 0.0),
+            JsAlign::XMaxYMin => (1.0, 0.0),
+            JsAlign::XMinYMid => (0.0, 0.5),
+            JsAlign::XMidYMid => (0.5, 0.5),
+            JsAlign::XMaxYMid => (1.0, 0.5),
+            JsAlign::XMinYMax => (0.0, 1.0),
+            JsAlign::XMidYMax => (0.5, 1.0),
+            JsAlign::XMaxYMax => (1.0, 1.0),
+        }
+    }
 }
 
 impl FitToDef {
-    pub(crate) fn fit_to(&self, size: usvg::Size) -> Result<(u32, u32, Transform), Error> {
+    /// Fits the SVG `size`, the resulting image size must be within the `limits`.
+    pub(crate) fn fit_to(
+        &self,
+        size: usvg::Size,
+        limits: &JsLimits,
+    ) -> Result<(u32, u32, Transform), Error> {
+        let (width, height, transform) = self.fit(size)?;
+        limits.check_size(width, height)?;
+        Ok((width, height, transform))
+    }
+
+    fn fit(&self, size: usvg::Size) -> Result<(u32, u32, Transform), Error> {
         let mut transform = Transform::identity();
         let width = size.width();
         let height = size.height();
@@ -42,6 +133,7 @@ impl FitToDef {
             FitToDef::Width(w) => *w as f32 / width,
             FitToDef::Height(h) => *h as f32 / height,
             FitToDef::Zoom(s) => *s,
+            FitToDef::Box(b) => return b.fit_to(size),
         };
         let width = (width * scale).round().max(0.0) as u32;
         let height = (height * scale).round().max(0.0) as u32;
@@ -57,6 +149,46 @@ impl FitToDef {
     }
 }
 
+impl JsFitBox {
+    fn fit_to(&self, size: usvg::Size) -> Result<(u32, u32, Transform), Error> {
+        let sx = self.width as f32 / size.width();
+        let sy = self.height as f32 / size.height();
+        let (width, height, transform) = match self.fit {
+            JsFit::Fill => (self.width, self.height, Transform::from_scale(sx, sy)),
+            JsFit::Contain | JsFit::Cover => {
+                let scale = match self.fit {
+                    JsFit::Contain => sx.min(sy),
+                    _ => sx.max(sy),
+                };
+                // The free space is negative when covering, which crops the image.
+                let (fx, fy) = self.align.factors();
+                let dx = (self.width as f32 - size.width() * scale) * fx;
+                let dy = (self.height as f32 - size.height() * scale) * fy;
+                let transform = Transform::from_scale(scale, scale).post_translate(dx, dy);
+                (self.width, self.height, transform)
+            }
+            JsFit::Inside | JsFit::Outside => {
+                let scale = match self.fit {
+                    JsFit::Inside => sx.min(sy),
+                    _ => sx.max(sy),
+                };
+                let width = (size.width() * scale).round().max(0.0) as u32;
+                let height = (size.height() * scale).round().max(0.0) as u32;
+                let transform = Transform::from_scale(
+                    width as f32 / size.width(),
+                    height as f32 / size.height(),
+                );
+                (width, height, transform)
+            }
+        };
+        if width == 0 || height == 0 {
+            Err(Error::ZeroSized)
+        } else {
+            Ok((width, height, transform))
+        }
+    }
+}
+
 #[derive(Deserialize)]
 #[serde(rename_all = "lowercase", remote = "log::LevelFilter")]
 enum LogLevelDef {
@@ -106,7 +238,7 @@ pub struct JsOptions {
     /// Impact units conversion.
     ///
     /// Note: This is not the DPI in the PNG file. Resvg does not change the DPI
-    ///  of the PNG file.
+    ///  of the PNG file, unless `asPng({ writeDpi: true })` is used.
     /// https://github.com/RazrFalcon/resvg/issues/451#issuecomment-914462093
     /// https://github.com/RazrFalcon/resvg/issues/526#issuecomment-1190433890
     ///
@@ -161,6 +293,9 @@ pub struct JsOptions {
 
     #[serde(with = "LogLevelDef")]
     pub log_level: log::LevelFilter,
+
+    /// Resource limits, to render untrusted SVGs.
+    pub limits: JsLimits,
 }
 
 impl Default for JsOptions {
@@ -176,20 +311,15 @@ impl Default for JsOptions {
             background: None,
             crop: JsCropOptions::default(),
             log_level: log::LevelFilter::Error,
+            limits: JsLimits::default(),
         }
     }
 }
 
 impl JsOptions {
-    pub(crate) fn to_usvg_options(&self) -> (usvg::Options, Database) {
-        // Load fonts
-        #[cfg(not(target_arch = "wasm32"))]
-        let fontdb = crate::fonts::load_fonts(&self.font);
-        #[cfg(target_arch = "wasm32")]
-        let fontdb = Database::new();
-
-        // Build the SVG options
-        let opts = usvg::Options {
+    /// Builds the SVG options, fonts are loaded separately, see `FontDatabase`.
+    pub(crate) fn to_usvg_options(&self) -> usvg::Options {
+        usvg::Options {
             resources_dir: None,
             dpi: self.dpi,
             font_family: self.font.default_font_family.clone(),
@@ -200,31 +330,44 @@ impl JsOptions {
             image_rendering: self.image_rendering,
             default_size: usvg::Size::from_wh(100.0, 100.0).unwrap(),
             image_href_resolver: usvg::ImageHrefResolver::default(),
-        };
-        (opts, fontdb)
+        }
     }
 
     pub(crate) fn create_pixmap(&self, width: u32, height: u32) -> Result<Pixmap, Error> {
-        // Parse the background
-        let background = self
-            .background
-            .as_ref()
-            .map(|color| color.parse::<svgtypes::Color>())
-            .transpose()?;
-
-        // Unwrap is safe, because `size` is already valid.
-        let mut pixmap = Pixmap::new(width, height).unwrap();
-
-        if let Some(bg) = background {
-            let color = resvg::tiny_skia::Color::from_rgba8(bg.red, bg.green, bg.blue, bg.alpha);
-            pixmap.fill(color);
-        }
-        Ok(pixmap)
+        create_pixmap(width, height, self.background.as_deref(), &self.limits)
+    }
+
+    pub(crate) fn background_color(&self) -> Result<Option<Color>, Error> {
+        parse_background(self.background.as_deref())
     }
 }
 
+pub(crate) fn create_pixmap(
+    width: u32,
+    height: u32,
+    background: Option<&str>,
+    limits: &JsLimits,
+) -> Result<Pixmap, Error> {
+    limits.check_size(width, height)?;
+    // `None` when the size overflows, e.g. a 1000x zoom without limits.
+    let mut pixmap = Pixmap::new(width, height).ok_or(Error::ImageTooLarge(width, height))?;
+
+    if let Some(color) = parse_background(background)? {
+        pixmap.fill(color);
+    }
+    Ok(pixmap)
+}
+
+/// Parses the background
+fn parse_background(background: Option<&str>) -> Result<Option<Color>, Error> {
+    let background = background
+        .map(|color| color.parse::<svgtypes::Color>())
+        .transpose()?;
+    Ok(background.map(|bg| Color::from_rgba8(bg.red, bg.green, bg.blue, bg.alpha)))
+}
+
 /// The font options passed to `load_fonts()`.
-#[derive(Deserialize)]
+#[derive(Deserialize, Clone)]
 #[serde(rename_all = "camelCase", default, deny_unknown_fields)]
 pub struct JsFontOptions {
     /// If system fonts should be loaded.
@@ -276,6 +419,11 @@ pub struct JsFontOptions {
     ///
     /// Default: Courier New
     pub monospace_family: String,
+
+    /// The families to take glyphs from when the font of a text lacks them.
+    ///
+    /// Default: []
+    pub fallback_families: JsFallbackFamilies,
 }
 
 impl Default for JsFontOptions {
@@ -291,8 +439,46 @@ impl Default for JsFontOptions {
             cursive_family: "Comic Sans MS".to_string(),
             fantasy_family: "Impact".to_string(),
             monospace_family: "Courier New".to_string(),
+            fallback_families: JsFallbackFamilies::default(),
+        }
+    }
+}
+
+/// Font fallback families, either an ordered list or ordered lists keyed by Unicode script name
+/// (`Latin`, `Han`, `Arabic`...). The `default` list of the keyed form is tried after the list
+/// of the script of the character.
+#[derive(Deserialize, Clone)]
+#[serde(untagged)]
+pub enum JsFallbackFamilies {
+    List(Vec<String>),
+    ByScript(BTreeMap<String, Vec<String>>),
+}
+
+impl Default for JsFallbackFamilies {
+    fn default() -> JsFallbackFamilies {
+        JsFallbackFamilies::List(vec![])
+    }
+}
+
+impl JsFallbackFamilies {
+    pub fn is_empty(&self) -> bool {
+        match self {
+            JsFallbackFamilies::List(families) => families.is_empty(),
+            JsFallbackFamilies::ByScript(scripts) => scripts.values().all(Vec::is_empty),
         }
     }
+
+    /// The families to try, in order, for a character of the given script.
+    pub fn families_for<'a>(&'a self, script: &'a str) -> impl Iterator<Item = &'a String> {
+        let (script, default): (&[String], &[String]) = match self {
+            JsFallbackFamilies::List(families) => (families, &[]),
+            JsFallbackFamilies::ByScript(scripts) => (
+                scripts.get(script).map_or(&[], Vec::as_slice),
+                scripts.get("default").map_or(&[], Vec::as_slice),
+            ),
+        };
+        script.iter().chain(default)
+    }
 }
 
 /// The font options passed to `load_fonts()`.
@@ -320,6 +506,388 @@ pub struct JsCropOptions {
     pub bottom: Option<i32>,
 }
 
+/// Resource limits, each one is unlimited when unset.
+#[derive(Deserialize, Default, Clone)]
+#[serde(rename_all = "camelCase", default, deny_unknown_fields)]
+pub struct JsLimits {
+    /// The maximum number of pixels of the rendered image, i.e. its width times its height.
+    ///
+    /// Default: None
+    pub max_pixels: Option<u64>,
+
+    /// The maximum width of the rendered image.
+    ///
+    /// Default: None
+    pub max_width: Option<u32>,
+
+    /// The maximum height of the rendered image.
+    ///
+    /// Default: None
+    pub max_height: Option<u32>,
+
+    /// The maximum number of elements, counting each `<use>` element as the elements it
+    /// references.
+    ///
+    /// Default: None
+    pub max_nodes: Option<u64>,
+
+    /// The maximum number of nested `<use>` references.
+    ///
+    /// Default: None
+    pub max_use_depth: Option<u32>,
+
+    /// The maximum number of pixels of a filter region, each filter primitive allocates an image
+    /// of this size.
+    ///
+    /// Default: None
+    pub max_filter_region: Option<u64>,
+}
+
+impl JsLimits {
+    /// Checks the size of the rendered image.
+    pub(crate) fn check_size(&self, width: u32, height: u32) -> Result<(), Error> {
+        if let Some(max_width) = self.max_width.filter(|max| width > *max) {
+            return Err(Error::WidthLimit(width, max_width));
+        }
+        if let Some(max_height) = self.max_height.filter(|max| height > *max) {
+            return Err(Error::HeightLimit(height, max_height));
+        }
+        let pixels = width as u64 * height as u64;
+        if let Some(max_pixels) = self.max_pixels.filter(|max| pixels > *max) {
+            return Err(Error::PixelsLimit(pixels, max_pixels));
+        }
+        Ok(())
+    }
+}
+
+/// One entry of the list passed to `Resvg.renderMany()`, unset options fall back to the render
+/// options.
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase", default, deny_unknown_fields)]
+pub struct JsRenderVariant {
+    pub fit_to: Option<FitToDef>,
+    pub crop: Option<JsCropOptions>,
+    pub background: Option<String>,
+}
+
+/// The options passed to `Resvg.renderNode()`.
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase", default, deny_unknown_fields)]
+pub struct JsRenderNodeOptions {
+    /// The size to render the node, its bounding box being the original size.
+    ///
+    /// Default: the `fitTo` render option
+    pub fit_to: Option<FitToDef>,
+}
+
+/// The options passed to `Resvg.renderInto()`.
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase", default, deny_unknown_fields)]
+pub struct JsRenderIntoOptions {
+    /// The number of bytes per row of the target buffer, a multiple of 4.
+    ///
+    /// Default: the rendered width * 4
+    pub stride: Option<u32>,
+
+    /// The horizontal position of the render in the target buffer, in pixels.
+    ///
+    /// Default: 0
+    pub x: u32,
+
+    /// The vertical position of the render in the target buffer, in pixels.
+    ///
+    /// Default: 0
+    pub y: u32,
+}
+
+/// The options passed to `RenderedImage.asPng()`.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase", default, deny_unknown_fields)]
+pub struct JsPngOptions {
+    /// Write the `dpi` render option to the pHYs chunk.
+    ///
+    /// Default: false
+    pub write_dpi: bool,
+
+    /// Text metadata, e.g. `{ Title: 'Logo', Source: '<hash>' }`.
+    ///
+    /// Latin-1 values are written as tEXt chunks, others as UTF-8 iTXt chunks.
+    ///
+    /// Default: {}
+    pub text: BTreeMap<String, String>,
+
+    /// Write an sRGB chunk (with the matching gAMA and cHRM chunks).
+    ///
+    /// Default: false
+    pub srgb: bool,
+
+    /// The deflate compression level: `fast`, `default`, `best` or `max`.
+    ///
+    /// `max` uses zopfli, it produces the smallest files but is much slower.
+    ///
+    /// Default: default
+    pub compression: JsPngCompression,
+
+    /// The scanline filter: `adaptive` picks the best filter for each line, while `none`, `sub`,
+    /// `up`, `average` and `paeth` use a fixed filter.
+    ///
+    /// Default: sub
+    pub filter: JsPngFilter,
+
+    /// Quantize the image to an 8-bit palette, with alpha written to the tRNS chunk.
+    ///
+    /// Default: false
+    pub palette: bool,
+
+    /// The maximum number of palette colors, from 2 to 256. Only used with `palette`.
+    ///
+    /// Default: 256
+    pub max_colors: u16,
+
+    /// Floyd-Steinberg dithering, when the image has more colors than `max_colors`.
+    ///
+    /// Default: false
+    pub dither: bool,
+}
+
+impl Default for JsPngOptions {
+    fn default() -> JsPngOptions {
+        JsPngOptions {
+            write_dpi: false,
+            text: BTreeMap::new(),
+            srgb: false,
+            compression: JsPngCompression::default(),
+            filter: JsPngFilter::default(),
+            palette: false,
+            max_colors: 256,
+            dither: false,
+        }
+    }
+}
+
+/// PNG deflate compression levels.
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "camelCase")]
+pub enum JsPngCompression {
+    /// Fast minimal compression, for large batch jobs.
+    Fast,
+    #[default]
+    Default,
+    /// The highest level of the `png` crate encoder.
+    Best,
+    /// Zopfli compression.
+    Max,
+}
+
+/// PNG scanline filters.
+#[derive(Deserialize, Clone, Copy, Default)]
+#[serde(rename_all = "camelCase")]
+pub enum JsPngFilter {
+    /// Picks the filter with the minimum sum of absolute differences for each line.
+    Adaptive,
+    None,
+    #[default]
+    Sub,
+    Up,
+    Average,
+    Paeth,
+}
+
+/// The options passed to `RenderedImage.getPixels()`.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase", default, deny_unknown_fields)]
+pub struct JsPixelsOptions {
+    /// Keep the colors premultiplied by alpha, like `RenderedImage.pixels`.
+    ///
+    /// Default: true
+    pub premultiplied: bool,
+
+    /// The channel order: `rgba`, `bgra`, `argb`, `rgb` or `gray`.
+    ///
+    /// `rgb` and `gray` drop the alpha channel.
+    ///
+    /// Default: rgba
+    pub format: JsPixelFormat,
+}
+
+impl Default for JsPixelsOptions {
+    fn default() -> JsPixelsOptions {
+        JsPixelsOptions {
+            premultiplied: true,
+            format: JsPixelFormat::Rgba,
+        }
+    }
+}
+
+/// Pixel formats of `RenderedImage.getPixels()`.
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "camelCase")]
+pub enum JsPixelFormat {
+    Rgba,
+    Bgra,
+    Argb,
+    Rgb,
+    /// 8-bit luma, using the Rec. 709 coefficients.
+    Gray,
+}
+
+/// The options passed to `RenderedImage.asWebp()`.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase", default, deny_unknown_fields)]
+pub struct JsWebpOptions {
+    /// The encoding quality, from 0 to 100.
+    ///
+    /// Ignored when `lossless` is enabled.
+    ///
+    /// Default: 80
+    pub quality: f32,
+
+    /// Use lossless compression.
+    ///
+    /// Default: false
+    pub lossless: bool,
+}
+
+impl Default for JsWebpOptions {
+    fn default() -> JsWebpOptions {
+        JsWebpOptions {
+            quality: 80.0,
+            lossless: false,
+        }
+    }
+}
+
+/// The options passed to `RenderedImage.asJpeg()`.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase", default, deny_unknown_fields)]
+pub struct JsJpegOptions {
+    /// The encoding quality, from 1 to 100.
+    ///
+    /// Default: 90
+    pub quality: f32,
+
+    /// The color used to flatten transparent pixels, JPEG has no alpha channel.
+    ///
+    /// Default: `None`, which uses the `background` of the render options, or white.
+    pub background: Option<String>,
+
+    /// Encode a progressive JPEG.
+    ///
+    /// Default: false
+    pub progressive: bool,
+
+    /// The chroma subsampling.
+    ///
+    /// Default: 4:2:0
+    pub chroma_subsampling: JsChromaSubsampling,
+}
+
+impl Default for JsJpegOptions {
+    fn default() -> JsJpegOptions {
+        JsJpegOptions {
+            quality: 90.0,
+            background: None,
+            progressive: false,
+            chroma_subsampling: JsChromaSubsampling::Yuv420,
+        }
+    }
+}
+
+/// JPEG chroma subsampling modes.
+#[derive(Deserialize, Clone, Copy)]
+pub enum JsChromaSubsampling {
+    /// No subsampling, the best quality for sharp edges and text.
+    #[serde(rename = "4:4:4")]
+    Yuv444,
+    /// Half horizontal chroma resolution.
+    #[serde(rename = "4:2:2")]
+    Yuv422,
+    /// Half horizontal and vertical chroma resolution.
+    #[serde(rename = "4:2:0")]
+    Yuv420,
+}
+
+/// The options passed to `RenderedImage.asAvif()`.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase", default, deny_unknown_fields)]
+pub struct JsAvifOptions {
+    /// The encoding quality, from 1 to 100.
+    ///
+    /// Default: 80
+    pub quality: f32,
+
+    /// The encoding speed, from 1 (slowest, smallest file) to 10 (fastest).
+    ///
+    /// Default: 6
+    pub speed: u8,
+}
+
+impl Default for JsAvifOptions {
+    fn default() -> JsAvifOptions {
+        JsAvifOptions {
+            quality: 80.0,
+            speed: 6,
+        }
+    }
+}
+
+/// Deserializes an options object passed from Node.js, either a JSON string or an object.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn from_json_value<T: DeserializeOwned + Default>(
+    value: Option<serde_json::Value>,
+) -> Result<T, Error> {
+    parse_options(value.unwrap_or(serde_json::Value::Null))
+}
+
+/// Deserializes the options, either a JSON string or an object.
+///
+/// Invalid options are reported with their path, e.g. `fitTo.mode`, instead of falling back to
+/// the default.
+pub(crate) fn parse_options<T: DeserializeOwned + Default>(
+    value: serde_json::Value,
+) -> Result<T, Error> {
+    let mut value = match value {
+        serde_json::Value::String(s) if s.trim().is_empty() => serde_json::Value::Null,
+        serde_json::Value::String(s) => serde_json::from_str(s.as_str())
+            .map_err(|e| Error::InvalidOptions(".".to_string(), e.to_string()))?,
+        value => value,
+    };
+    remove_null_properties(&mut value);
+    if value.is_null() {
+        return Ok(T::default());
+    }
+    serde_path_to_error::deserialize(value)
+        .map_err(|e| Error::InvalidOptions(e.path().to_string(), e.into_inner().to_string()))
+}
+
+/// Removes the `null` properties, so that they fall back to their default like the `undefined`
+/// ones, e.g. `{ background: opts.background }` when `opts.background` is not set.
+fn remove_null_properties(value: &mut serde_json::Value) {
+    match value {
+        serde_json::Value::Object(map) => {
+            map.retain(|_, value| !value.is_null());
+            map.values_mut().for_each(remove_null_properties);
+        }
+        serde_json::Value::Array(values) => values.iter_mut().for_each(remove_null_properties),
+        _ => {}
+    }
+}
+
+/// Deserializes the options passed from Wasm, either a JSON string or an object.
+#[cfg(target_arch = "wasm32")]
+pub(crate) fn parse_js_options<T: DeserializeOwned + Default>(value: &JsValue) -> Result<T, Error> {
+    if value.is_undefined() || value.is_null() {
+        return Ok(T::default());
+    }
+    if let Some(s) = value.as_string() {
+        return parse_options(serde_json::Value::String(s));
+    }
+    let json = js_sys::JSON::stringify(value)
+        .map(String::from)
+        .map_err(|_| Error::InvalidOptions(".".to_string(), "not serializable".to_string()))?;
+    parse_options(serde_json::Value::String(json))
+}
+
 /// Deserializes `usvg::ShapeRendering`
 fn deserialize_shape_rendering<'de, D>(deserializer: D) -> Result<usvg::ShapeRendering, D::Error>
 where
@@ -0,0 +1,1023 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use log::warn;
+use miniz_oxide::deflate::compress_to_vec_zlib;
+use pdf_writer::{
+    types::{ColorSpaceOperand, FunctionShadingType, LineCapStyle, LineJoinStyle, MaskType},
+    Content, Filter, Finish, Name, Pdf, Rect, Ref,
+};
+use resvg::{
+    tiny_skia::{self, PathSegment, Pixmap, Point, Transform},
+    usvg::{self, ImageKind, NodeKind},
+};
+
+/// Converts the usvg tree to a single page vector PDF, sized to `tree.size`.
+///
+/// Text must already be converted to paths. Filters and patterns are not supported and are
+/// skipped with a warning, gradient stop opacities are ignored. The `reflect` and `repeat`
+/// spread methods are padded with a warning when the gradient would be repeated more than
+/// `GradientShape::MAX_REPETITIONS` times.
+pub(crate) fn tree_to_pdf(tree: &usvg::Tree) -> Vec<u8> {
+    let mut writer = PdfWriter {
+        pdf: Pdf::new(),
+        next_id: 1,
+    };
+    let catalog_id = writer.alloc();
+    let page_tree_id = writer.alloc();
+    let page_id = writer.alloc();
+    let content_id = writer.alloc();
+
+    let width = tree.size.width();
+    let height = tree.size.height();
+
+    // PDF user space has its origin at the bottom-left corner, flip it to match SVG.
+    let mut stream = Stream::new();
+    stream.concat(Transform::from_row(1.0, 0.0, 0.0, -1.0, 0.0, height));
+    stream.concat(usvg::utils::view_box_to_transform(
+        tree.view_box.rect,
+        tree.view_box.aspect,
+        tree.size,
+    ));
+    writer.render_children(&tree.root, &mut stream);
+
+    writer.pdf.catalog(catalog_id).pages(page_tree_id);
+    writer.pdf.pages(page_tree_id).kids([page_id]).count(1);
+    let mut page = writer.pdf.page(page_id);
+    page.media_box(Rect::new(0.0, 0.0, width, height))
+        .parent(page_tree_id)
+        .contents(content_id);
+    stream.resources.write(&mut page.resources());
+    page.finish();
+
+    let content = compress_to_vec_zlib(&stream.content.finish(), 6);
+    writer
+        .pdf
+        .stream(content_id, &content)
+        .filter(Filter::FlateDecode);
+
+    writer.pdf.finish()
+}
+
+/// Resources referenced by a content stream.
+#[derive(Default)]
+struct Resources {
+    ext_g_states: Vec<Ref>,
+    patterns: Vec<Ref>,
+    x_objects: Vec<Ref>,
+}
+
+impl Resources {
+    fn ext_g_state(&mut self, id: Ref) -> String {
+        self.ext_g_states.push(id);
+        format!("G{}", self.ext_g_states.len())
+    }
+
+    fn pattern(&mut self, id: Ref) -> String {
+        self.patterns.push(id);
+        format!("P{}", self.patterns.len())
+    }
+
+    fn x_object(&mut self, id: Ref) -> String {
+        self.x_objects.push(id);
+        format!("X{}", self.x_objects.len())
+    }
+
+    fn write(&self, resources: &mut pdf_writer::writers::Resources) {
+        let mut ext_g_states = resources.ext_g_states();
+        for (i, id) in self.ext_g_states.iter().enumerate() {
+            ext_g_states.pair(Name(format!("G{}", i + 1).as_bytes()), *id);
+        }
+        ext_g_states.finish();
+
+        let mut patterns = resources.patterns();
+        for (i, id) in self.patterns.iter().enumerate() {
+            patterns.pair(Name(format!("P{}", i + 1).as_bytes()), *id);
+        }
+        patterns.finish();
+
+        let mut x_objects = resources.x_objects();
+        for (i, id) in self.x_objects.iter().enumerate() {
+            x_objects.pair(Name(format!("X{}", i + 1).as_bytes()), *id);
+        }
+        x_objects.finish();
+    }
+}
+
+/// A content stream of the page or of a form XObject.
+struct Stream {
+    content: Content,
+    resources: Resources,
+    /// The transform from the current user space to the space of the stream,
+    /// which is also the pattern space.
+    ts: Transform,
+    saved: Vec<Transform>,
+}
+
+impl Stream {
+    fn new() -> Self {
+        Stream {
+            content: Content::new(),
+            resources: Resources::default(),
+            ts: Transform::identity(),
+            saved: vec![],
+        }
+    }
+
+    fn save(&mut self) {
+        self.content.save_state();
+        self.saved.push(self.ts);
+    }
+
+    fn restore(&mut self) {
+        self.content.restore_state();
+        self.ts = self.saved.pop().unwrap_or_default();
+    }
+
+    fn concat(&mut self, ts: Transform) {
+        if !ts.is_identity() {
+            self.content.transform(to_matrix(ts));
+            self.ts = self.ts.pre_concat(ts);
+        }
+    }
+
+    fn set_ext_g_state(&mut self, id: Ref) {
+        let name = self.resources.ext_g_state(id);
+        self.content.set_parameters(Name(name.as_bytes()));
+    }
+}
+
+struct PdfWriter {
+    pdf: Pdf,
+    next_id: i32,
+}
+
+impl PdfWriter {
+    fn alloc(&mut self) -> Ref {
+        let id = Ref::new(self.next_id);
+        self.next_id += 1;
+        id
+    }
+
+    fn render_children(&mut self, parent: &usvg::Node, stream: &mut Stream) {
+        for node in parent.children() {
+            self.render_node(&node, stream);
+        }
+    }
+
+    fn render_node(&mut self, node: &usvg::Node, stream: &mut Stream) {
+        match &*node.borrow() {
+            NodeKind::Group(g) => self.render_group(node, g, stream),
+            NodeKind::Path(p) => self.render_path(p, stream),
+            NodeKind::Image(image) => self.render_image(image, stream),
+            // Text is converted to paths by `convert_text`.
+            NodeKind::Text(_) => {}
+        }
+    }
+
+    fn render_group(&mut self, node: &usvg::Node, g: &usvg::Group, stream: &mut Stream) {
+        if !g.filters.is_empty() {
+            warn!(
+                "PDF export does not support filters, they are ignored on '{}'.",
+                g.id
+            );
+        }
+
+        stream.save();
+        stream.concat(g.transform);
+
+        let bbox = children_bbox(node);
+        if let Some(clip_path) = &g.clip_path {
+            self.apply_clip_path(clip_path, bbox, stream);
+        }
+
+        if g.opacity.get() >= 1.0 && g.mask.is_none() {
+            self.render_children(node, stream);
+            stream.restore();
+            return;
+        }
+
+        // The opacity and the mask apply to the children as a whole,
+        // so they are isolated in a transparency group.
+        let Some(bbox) = bbox else {
+            stream.restore();
+            return;
+        };
+        let mask_id = g.mask.as_ref().map(|mask| self.write_mask(mask, bbox));
+
+        let form_id = self.alloc();
+        let mut inner = Stream::new();
+        self.render_children(node, &mut inner);
+        self.write_form(form_id, inner, bbox, None);
+
+        let gs_id = self.alloc();
+        let mut gs = self.pdf.ext_graphics(gs_id);
+        gs.non_stroking_alpha(g.opacity.get())
+            .stroking_alpha(g.opacity.get());
+        if let (Some(mask), Some(mask_id)) = (&g.mask, mask_id) {
+            gs.soft_mask().subtype(mask_type(mask.kind)).group(mask_id);
+        }
+        gs.finish();
+
+        stream.set_ext_g_state(gs_id);
+        let name = stream.resources.x_object(form_id);
+        stream.content.x_object(Name(name.as_bytes()));
+        stream.restore();
+    }
+
+    /// Writes a transparency group form XObject.
+    ///
+    /// `mask_type` is set when the form is used as a soft mask.
+    fn write_form(&mut self, id: Ref, stream: Stream, bbox: Rect, mask_type: Option<MaskType>) {
+        let content = compress_to_vec_zlib(&stream.content.finish(), 6);
+        let mut form = self.pdf.form_xobject(id, &content);
+        form.bbox(bbox);
+        form.filter(Filter::FlateDecode);
+        let mut group = form.group();
+        group.transparency().isolated(true);
+        if mask_type == Some(MaskType::Luminosity) {
+            group.color_space().device_rgb();
+        }
+        group.finish();
+        stream.resources.write(&mut form.resources());
+    }
+
+    /// Writes the mask content as a soft mask group, returns the form XObject.
+    fn write_mask(&mut self, mask: &usvg::Mask, bbox: Rect) -> Ref {
+        let rect = if mask.units == usvg::Units::ObjectBoundingBox {
+            map_rect(bbox_transform(bbox), to_pdf_rect(mask.rect))
+        } else {
+            to_pdf_rect(mask.rect)
+        };
+
+        let mut inner = Stream::new();
+        inner
+            .content
+            .rect(rect.x1, rect.y1, rect.x2 - rect.x1, rect.y2 - rect.y1)
+            .clip_nonzero()
+            .end_path();
+
+        // A mask on the mask element limits the mask content itself.
+        if let Some(sub_mask) = &mask.mask {
+            let sub_mask_id = self.write_mask(sub_mask, bbox);
+            let gs_id = self.alloc();
+            self.pdf
+                .ext_graphics(gs_id)
+                .soft_mask()
+                .subtype(mask_type(sub_mask.kind))
+                .group(sub_mask_id);
+            inner.set_ext_g_state(gs_id);
+        }
+
+        if mask.content_units == usvg::Units::ObjectBoundingBox {
+            inner.concat(bbox_transform(bbox));
+        }
+        self.render_children(&mask.root, &mut inner);
+
+        let id = self.alloc();
+        self.write_form(id, inner, rect, Some(mask_type(mask.kind)));
+        id
+    }
+
+    /// Intersects the current clip with the clip path, all its paths are combined into one.
+    fn apply_clip_path(
+        &mut self,
+        clip_path: &usvg::ClipPath,
+        bbox: Option<Rect>,
+        stream: &mut Stream,
+    ) {
+        if let Some(sub_clip_path) = &clip_path.clip_path {
+            self.apply_clip_path(sub_clip_path, bbox, stream);
+        }
+
+        let mut ts = clip_path.transform;
+        if clip_path.units == usvg::Units::ObjectBoundingBox {
+            match bbox {
+                Some(bbox) => ts = ts.pre_concat(bbox_transform(bbox)),
+                None => {
+                    // An element without a bounding box is clipped entirely.
+                    stream
+                        .content
+                        .rect(0.0, 0.0, 0.0, 0.0)
+                        .clip_nonzero()
+                        .end_path();
+                    return;
+                }
+            }
+        }
+
+        let mut even_odd = true;
+        let mut is_empty = true;
+        collect_clip_paths(&clip_path.root, ts, &mut |path, rule| {
+            write_path(&path, &mut stream.content);
+            even_odd &= rule == usvg::FillRule::EvenOdd;
+            is_empty = false;
+        });
+        if is_empty {
+            stream.content.rect(0.0, 0.0, 0.0, 0.0);
+        }
+        if even_odd && !is_empty {
+            stream.content.clip_even_odd();
+        } else {
+            stream.content.clip_nonzero();
+        }
+        stream.content.end_path();
+    }
+
+    fn render_path(&mut self, p: &usvg::Path, stream: &mut Stream) {
+        if p.visibility != usvg::Visibility::Visible {
+            return;
+        }
+
+        stream.save();
+        stream.concat(p.transform);
+        match p.paint_order {
+            usvg::PaintOrder::FillAndStroke => {
+                self.fill_path(p, stream);
+                self.stroke_path(p, stream);
+            }
+            usvg::PaintOrder::StrokeAndFill => {
+                self.stroke_path(p, stream);
+                self.fill_path(p, stream);
+            }
+        }
+        stream.restore();
+    }
+
+    fn fill_path(&mut self, p: &usvg::Path, stream: &mut Stream) {
+        let Some(fill) = &p.fill else {
+            return;
+        };
+        if !self.set_paint(p, &fill.paint, fill.opacity, false, stream) {
+            return;
+        }
+
+        write_path(&p.data, &mut stream.content);
+        match fill.rule {
+            usvg::FillRule::NonZero => stream.content.fill_nonzero(),
+            usvg::FillRule::EvenOdd => stream.content.fill_even_odd(),
+        };
+    }
+
+    fn stroke_path(&mut self, p: &usvg::Path, stream: &mut Stream) {
+        let Some(stroke) = &p.stroke else {
+            return;
+        };
+        if !self.set_paint(p, &stroke.paint, stroke.opacity, true, stream) {
+            return;
+        }
+
+        let content = &mut stream.content;
+        content.set_line_width(stroke.width.get());
+        content.set_miter_limit(stroke.miterlimit.get());
+        content.set_line_cap(match stroke.linecap {
+            usvg::LineCap::Butt => LineCapStyle::ButtCap,
+            usvg::LineCap::Round => LineCapStyle::RoundCap,
+            usvg::LineCap::Square => LineCapStyle::ProjectingSquareCap,
+        });
+        content.set_line_join(match stroke.linejoin {
+            usvg::LineJoin::Round => LineJoinStyle::RoundJoin,
+            usvg::LineJoin::Bevel => LineJoinStyle::BevelJoin,
+            _ => LineJoinStyle::MiterJoin,
+        });
+        if let Some(dasharray) = &stroke.dasharray {
+            content.set_dash_pattern(dasharray.iter().copied(), stroke.dashoffset);
+        }
+
+        write_path(&p.data, content);
+        content.stroke();
+    }
+
+    /// Sets the fill or stroke paint, returns `false` if nothing should be painted.
+    fn set_paint(
+        &mut self,
+        p: &usvg::Path,
+        paint: &usvg::Paint,
+        opacity: usvg::Opacity,
+        is_stroke: bool,
+        stream: &mut Stream,
+    ) -> bool {
+        match paint {
+            usvg::Paint::Color(c) => {
+                let (r, g, b) = (
+                    c.red as f32 / 255.0,
+                    c.green as f32 / 255.0,
+                    c.blue as f32 / 255.0,
+                );
+                if is_stroke {
+                    stream.content.set_stroke_rgb(r, g, b);
+                } else {
+                    stream.content.set_fill_rgb(r, g, b);
+                }
+            }
+            usvg::Paint::LinearGradient(lg) => {
+                let shape = GradientShape::Linear([lg.x1, lg.y1, lg.x2, lg.y2]);
+                let Some(id) = self.write_gradient(p, &lg.id, &lg.base, shape, stream) else {
+                    return false;
+                };
+                set_pattern(id, is_stroke, stream);
+            }
+            usvg::Paint::RadialGradient(rg) => {
+                let shape = GradientShape::Radial([rg.fx, rg.fy, rg.cx, rg.cy, rg.r.get()]);
+                let Some(id) = self.write_gradient(p, &rg.id, &rg.base, shape, stream) else {
+                    return false;
+                };
+                set_pattern(id, is_stroke, stream);
+            }
+            usvg::Paint::Pattern(pattern) => {
+                warn!(
+                    "PDF export does not support patterns, '{}' is skipped.",
+                    pattern.id
+                );
+                return false;
+            }
+        }
+
+        if opacity.get() < 1.0 {
+            let gs_id = self.alloc();
+            let mut gs = self.pdf.ext_graphics(gs_id);
+            if is_stroke {
+                gs.stroking_alpha(opacity.get());
+            } else {
+                gs.non_stroking_alpha(opacity.get());
+            }
+            gs.finish();
+            stream.set_ext_g_state(gs_id);
+        }
+        true
+    }
+
+    /// Writes the gradient as a shading pattern, returns the pattern.
+    ///
+    /// PDF shadings can only pad. A `reflect` or `repeat` gradient is stretched over every
+    /// repetition needed to cover the path, with the stops repeated, mirrored for every other
+    /// one when reflected.
+    fn write_gradient(
+        &mut self,
+        p: &usvg::Path,
+        id: &str,
+        gradient: &usvg::BaseGradient,
+        shape: GradientShape,
+        stream: &Stream,
+    ) -> Option<Ref> {
+        let bounds = p.data.bounds();
+        let mut gradient_ts = Transform::identity();
+        if gradient.units == usvg::Units::ObjectBoundingBox {
+            if bounds.width() <= 0.0 || bounds.height() <= 0.0 {
+                return None;
+            }
+            let bbox = Rect::new(bounds.left(), bounds.top(), bounds.right(), bounds.bottom());
+            gradient_ts = bbox_transform(bbox);
+        }
+        gradient_ts = gradient_ts.pre_concat(gradient.transform);
+
+        let stops_id = self.write_stops(&gradient.stops)?;
+        let (shape, function_id) = match gradient.spread_method {
+            usvg::SpreadMethod::Pad => (shape, stops_id),
+            spread_method => {
+                let extent = p.stroke.as_ref().map_or(0.0, stroke_extent);
+                let area = Rect::new(
+                    bounds.left() - extent,
+                    bounds.top() - extent,
+                    bounds.right() + extent,
+                    bounds.bottom() + extent,
+                );
+                match gradient_ts
+                    .invert()
+                    .and_then(|ts| shape.repetitions(map_rect(ts, area)))
+                {
+                    Some((first, last)) => {
+                        let reflect = spread_method == usvg::SpreadMethod::Reflect;
+                        let id = self.write_repeated_stops(stops_id, first, last, reflect);
+                        (shape.stretched(first as f32, last as f32), id)
+                    }
+                    None => {
+                        warn!(
+                            "PDF export does not support the spread method of the gradient '{}', it is padded.",
+                            id
+                        );
+                        (shape, stops_id)
+                    }
+                }
+            }
+        };
+
+        let pattern_id = self.alloc();
+        let mut pattern = self.pdf.shading_pattern(pattern_id);
+        let mut shading = pattern.function_shading();
+        shading
+            .shading_type(shape.shading_type())
+            .coords(shape.coords())
+            .extend([true, true])
+            .function(function_id);
+        shading.color_space().device_rgb();
+        shading.finish();
+        pattern.matrix(to_matrix(stream.ts.pre_concat(gradient_ts)));
+        pattern.finish();
+        Some(pattern_id)
+    }
+
+    /// Writes a stitching function that repeats the stops function from the repetition `first`
+    /// to `last`, the odd ones are mirrored when `reflect` is set.
+    fn write_repeated_stops(&mut self, stops_id: Ref, first: i32, last: i32, reflect: bool) -> Ref {
+        let count = (last - first) as f32;
+        let id = self.alloc();
+        self.pdf
+            .stitching_function(id)
+            .domain([0.0, 1.0])
+            .functions((first..last).map(|_| stops_id))
+            .bounds((1..last - first).map(|i| i as f32 / count))
+            .encode((first..last).flat_map(|i| {
+                if reflect && i.rem_euclid(2) == 1 {
+                    [1.0, 0.0]
+                } else {
+                    [0.0, 1.0]
+                }
+            }));
+        id
+    }
+
+    /// Writes the gradient stops as a stitching function of linear interpolations.
+    fn write_stops(&mut self, stops: &[usvg::Stop]) -> Option<Ref> {
+        let to_rgb = |stop: &usvg::Stop| {
+            [
+                stop.color.red as f32 / 255.0,
+                stop.color.green as f32 / 255.0,
+                stop.color.blue as f32 / 255.0,
+            ]
+        };
+        let mut points: Vec<(f32, [f32; 3])> = stops
+            .iter()
+            .map(|stop| (stop.offset.get(), to_rgb(stop)))
+            .collect();
+        let (first, last) = (*points.first()?, *points.last()?);
+        if first.0 > 0.0 {
+            points.insert(0, (0.0, first.1));
+        }
+        if last.0 < 1.0 || points.len() == 1 {
+            points.push((1.0, last.1));
+        }
+
+        let mut functions = vec![];
+        for pair in points.windows(2) {
+            let id = self.alloc();
+            self.pdf
+                .exponential_function(id)
+                .domain([0.0, 1.0])
+                .c0(pair[0].1)
+                .c1(pair[1].1)
+                .n(1.0);
+            functions.push(id);
+        }
+
+        let id = self.alloc();
+        let bounds = points[1..points.len() - 1]
+            .iter()
+            .map(|(offset, _)| *offset);
+        self.pdf
+            .stitching_function(id)
+            .domain([0.0, 1.0])
+            .functions(functions.iter().copied())
+            .bounds(bounds)
+            .encode(functions.iter().flat_map(|_| [0.0, 1.0]));
+        Some(id)
+    }
+
+    fn render_image(&mut self, image: &usvg::Image, stream: &mut Stream) {
+        if image.visibility != usvg::Visibility::Visible {
+            return;
+        }
+
+        stream.save();
+        stream.concat(image.transform);
+        let rect = image.view_box.rect;
+        stream
+            .content
+            .rect(rect.x(), rect.y(), rect.width(), rect.height())
+            .clip_nonzero()
+            .end_path();
+
+        let xobject = match &image.kind {
+            ImageKind::JPEG(data) => self.write_jpeg(data),
+            ImageKind::PNG(data) => match Pixmap::decode_png(data) {
+                Ok(pix) => Some(self.write_pixmap(&pix)),
+                Err(e) => {
+                    warn!("Failed to decode the PNG image '{}' cause {}.", image.id, e);
+                    None
+                }
+            },
+            ImageKind::SVG(tree) => {
+                let size =
+                    usvg::NonZeroRect::from_xywh(0.0, 0.0, tree.size.width(), tree.size.height());
+                if let Some(size) = size {
+                    stream.concat(view_box_transform(&image.view_box, size));
+                    stream.concat(usvg::utils::view_box_to_transform(
+                        tree.view_box.rect,
+                        tree.view_box.aspect,
+                        tree.size,
+                    ));
+                    self.render_children(&tree.root, stream);
+                }
+                None
+            }
+            _ => {
+                warn!(
+                    "PDF export does not support the image '{}', it is skipped.",
+                    image.id
+                );
+                None
+            }
+        };
+
+        if let Some((id, width, height)) = xobject {
+            let size = usvg::NonZeroRect::from_xywh(0.0, 0.0, width as f32, height as f32);
+            if let Some(size) = size {
+                stream.concat(view_box_transform(&image.view_box, size));
+                // An image XObject fills the unit square, with its first row at the top.
+                stream.concat(Transform::from_row(
+                    width as f32,
+                    0.0,
+                    0.0,
+                    -(height as f32),
+                    0.0,
+                    height as f32,
+                ));
+                let name = stream.resources.x_object(id);
+                stream.content.x_object(Name(name.as_bytes()));
+            }
+        }
+        stream.restore();
+    }
+
+    /// Embeds the JPEG data as is, returns the image XObject and its size.
+    fn write_jpeg(&mut self, data: &[u8]) -> Option<(Ref, u32, u32)> {
+        let Some((width, height, components)) = jpeg_info(data) else {
+            warn!("Failed to read the JPEG image header, it is skipped.");
+            return None;
+        };
+        let id = self.alloc();
+        let mut xobject = self.pdf.image_xobject(id, data);
+        xobject
+            .width(width as i32)
+            .height(height as i32)
+            .bits_per_component(8);
+        xobject.filter(Filter::DctDecode);
+        match components {
+            1 => xobject.color_space().device_gray(),
+            // Adobe CMYK JPEGs are stored inverted.
+            4 => {
+                xobject.color_space().device_cmyk();
+                xobject.decode([1.0, 0.0, 1.0, 0.0, 1.0, 0.0, 1.0, 0.0]);
+            }
+            _ => xobject.color_space().device_rgb(),
+        }
+        xobject.finish();
+        Some((id, width, height))
+    }
+
+    /// Embeds the pixmap with an alpha soft mask, returns the image XObject and its size.
+    fn write_pixmap(&mut self, pix: &Pixmap) -> (Ref, u32, u32) {
+        let mut rgb = Vec::with_capacity(pix.pixels().len() * 3);
+        let mut alpha = Vec::with_capacity(pix.pixels().len());
+        for pixel in pix.pixels() {
+            let c = pixel.demultiply();
+            rgb.extend_from_slice(&[c.red(), c.green(), c.blue()]);
+            alpha.push(c.alpha());
+        }
+
+        let s_mask_id = if alpha.iter().any(|a| *a != 255) {
+            let id = self.alloc();
+            let alpha = compress_to_vec_zlib(&alpha, 6);
+            let mut s_mask = self.pdf.image_xobject(id, &alpha);
+            s_mask
+                .width(pix.width() as i32)
+                .height(pix.height() as i32)
+                .bits_per_component(8);
+            s_mask.filter(Filter::FlateDecode);
+            s_mask.color_space().device_gray();
+            s_mask.finish();
+            Some(id)
+        } else {
+            None
+        };
+
+        let id = self.alloc();
+        let rgb = compress_to_vec_zlib(&rgb, 6);
+        let mut xobject = self.pdf.image_xobject(id, &rgb);
+        xobject
+            .width(pix.width() as i32)
+            .height(pix.height() as i32)
+            .bits_per_component(8);
+        xobject.filter(Filter::FlateDecode);
+        xobject.color_space().device_rgb();
+        if let Some(s_mask_id) = s_mask_id {
+            xobject.s_mask(s_mask_id);
+        }
+        xobject.finish();
+        (id, pix.width(), pix.height())
+    }
+}
+
+fn set_pattern(id: Ref, is_stroke: bool, stream: &mut Stream) {
+    let name = stream.resources.pattern(id);
+    if is_stroke {
+        stream
+            .content
+            .set_stroke_color_space(ColorSpaceOperand::Pattern);
+        stream
+            .content
+            .set_stroke_pattern(None, Name(name.as_bytes()));
+    } else {
+        stream
+            .content
+            .set_fill_color_space(ColorSpaceOperand::Pattern);
+        stream.content.set_fill_pattern(None, Name(name.as_bytes()));
+    }
+}
+
+fn mask_type(kind: usvg::MaskType) -> MaskType {
+    match kind {
+        usvg::MaskType::Luminance => MaskType::Luminosity,
+        usvg::MaskType::Alpha => MaskType::Alpha,
+    }
+}
+
+/// Visits every path of a clip path, transformed to the user space of the clipped element.
+fn collect_clip_paths(
+    parent: &usvg::Node,
+    ts: Transform,
+    f: &mut impl FnMut(tiny_skia::Path, usvg::FillRule),
+) {
+    for node in parent.children() {
+        match &*node.borrow() {
+            NodeKind::Path(p) => {
+                let rule = p.fill.as_ref().map(|f| f.rule).unwrap_or_default();
+                if let Some(path) = (*p.data).clone().transform(ts.pre_concat(p.transform)) {
+                    f(path, rule);
+                }
+            }
+            NodeKind::Group(g) => collect_clip_paths(&node, ts.pre_concat(g.transform), f),
+            _ => {}
+        }
+    }
+}
+
+/// Calculates the bounding box of the children in the user space of the parent.
+///
+/// Stroke widths are included, so the box may be slightly larger than the actual content.
+fn children_bbox(parent: &usvg::Node) -> Option<Rect> {
+    let mut bbox: Option<Rect> = None;
+    for node in parent.children() {
+        let rect = match &*node.borrow() {
+            NodeKind::Path(p) => {
+                let bounds = p.data.bounds();
+                let extent = p.stroke.as_ref().map_or(0.0, stroke_extent);
+                let rect = Rect::new(
+                    bounds.left() - extent,
+                    bounds.top() - extent,
+                    bounds.right() + extent,
+                    bounds.bottom() + extent,
+                );
+                Some(map_rect(p.transform, rect))
+            }
+            NodeKind::Group(g) => children_bbox(&node).map(|rect| map_rect(g.transform, rect)),
+            NodeKind::Image(image) => {
+                Some(map_rect(image.transform, to_pdf_rect(image.view_box.rect)))
+            }
+            NodeKind::Text(_) => None,
+        };
+        if let Some(rect) = rect {
+            bbox = Some(match bbox {
+                Some(bbox) => Rect::new(
+                    bbox.x1.min(rect.x1),
+                    bbox.y1.min(rect.y1),
+                    bbox.x2.max(rect.x2),
+                    bbox.y2.max(rect.y2),
+                ),
+                None => rect,
+            });
+        }
+    }
+    bbox
+}
+
+/// How far the stroke may reach out of the path: half the width, up to the miter limit times
+/// that at miter joins, and the diagonal of square caps.
+fn stroke_extent(stroke: &usvg::Stroke) -> f32 {
+    let mut scale = match stroke.linejoin {
+        usvg::LineJoin::Round | usvg::LineJoin::Bevel => 1.0,
+        _ => stroke.miterlimit.get(),
+    };
+    if stroke.linecap == usvg::LineCap::Square {
+        scale = scale.max(std::f32::consts::SQRT_2);
+    }
+    stroke.width.get() * scale / 2.0
+}
+
+/// The geometry of a gradient, in the gradient space.
+#[derive(Clone, Copy)]
+enum GradientShape {
+    /// `x1, y1, x2, y2`
+    Linear([f32; 4]),
+    /// `fx, fy, cx, cy, r`, the gradient goes from the focal point to the circle.
+    Radial([f32; 5]),
+}
+
+impl GradientShape {
+    /// The most repetitions a `reflect` or `repeat` gradient is stretched over.
+    const MAX_REPETITIONS: i32 = 256;
+
+    fn shading_type(&self) -> FunctionShadingType {
+        match self {
+            GradientShape::Linear(_) => FunctionShadingType::Axial,
+            GradientShape::Radial(_) => FunctionShadingType::Radial,
+        }
+    }
+
+    fn coords(&self) -> Vec<f32> {
+        match *self {
+            GradientShape::Linear(coords) => coords.to_vec(),
+            GradientShape::Radial([fx, fy, cx, cy, r]) => vec![fx, fy, 0.0, cx, cy, r],
+        }
+    }
+
+    /// The range of repetitions, 0 being the gradient itself, that covers the area. `None` when
+    /// the gradient is degenerate or would be repeated too many times.
+    fn repetitions(&self, area: Rect) -> Option<(i32, i32)> {
+        let corners = [
+            (area.x1, area.y1),
+            (area.x2, area.y1),
+            (area.x1, area.y2),
+            (area.x2, area.y2),
+        ];
+        let (min, max) = match *self {
+            GradientShape::Linear([x1, y1, x2, y2]) => {
+                let (dx, dy) = (x2 - x1, y2 - y1);
+                let length = dx * dx + dy * dy;
+                if length <= f32::EPSILON {
+                    return None;
+                }
+                // The position of the corners along the gradient vector.
+                let t = corners.map(|(x, y)| ((x - x1) * dx + (y - y1) * dy) / length);
+                let min = t.iter().copied().fold(f32::INFINITY, f32::min);
+                let max = t.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+                (min, max)
+            }
+            GradientShape::Radial([fx, fy, cx, cy, r]) => {
+                let (dx, dy) = (cx - fx, cy - fy);
+                // The circles only grow around the focal point when it is inside the circle.
+                let a = r * r - dx * dx - dy * dy;
+                if a <= f32::EPSILON {
+                    return None;
+                }
+                // The circle that goes through each corner, `|e - t * d| = t * r`.
+                let t = corners.map(|(x, y)| {
+                    let (ex, ey) = (x - fx, y - fy);
+                    let ed = ex * dx + ey * dy;
+                    (-ed + (ed * ed + a * (ex * ex + ey * ey)).sqrt()) / a
+                });
+                (0.0, t.iter().copied().fold(0.0, f32::max))
+            }
+        };
+        let (first, last) = (min.floor(), max.ceil().max(min.floor() + 1.0));
+        if last - first > Self::MAX_REPETITIONS as f32 {
+            return None;
+        }
+        Some((first as i32, last as i32))
+    }
+
+    /// The shape that goes from the repetition `first` to `last`.
+    fn stretched(&self, first: f32, last: f32) -> Self {
+        match *self {
+            GradientShape::Linear([x1, y1, x2, y2]) => {
+                let (dx, dy) = (x2 - x1, y2 - y1);
+                GradientShape::Linear([
+                    x1 + first * dx,
+                    y1 + first * dy,
+                    x1 + last * dx,
+                    y1 + last * dy,
+                ])
+            }
+            // The repetitions of a radial gradient start at the focal point.
+            GradientShape::Radial([fx, fy, cx, cy, r]) => GradientShape::Radial([
+                fx,
+                fy,
+                fx + last * (cx - fx),
+                fy + last * (cy - fy),
+                last * r,
+            ]),
+        }
+    }
+}
+
+/// Maps the rect by the transform and returns the bounding box of the result.
+fn map_rect(ts: Transform, rect: Rect) -> Rect {
+    let mut pts = [
+        Point::from_xy(rect.x1, rect.y1),
+        Point::from_xy(rect.x2, rect.y1),
+        Point::from_xy(rect.x1, rect.y2),
+        Point::from_xy(rect.x2, rect.y2),
+    ];
+    ts.map_points(&mut pts);
+    let x_min = pts.iter().map(|p| p.x).fold(f32::INFINITY, f32::min);
+    let y_min = pts.iter().map(|p| p.y).fold(f32::INFINITY, f32::min);
+    let x_max = pts.iter().map(|p| p.x).fold(f32::NEG_INFINITY, f32::max);
+    let y_max = pts.iter().map(|p| p.y).fold(f32::NEG_INFINITY, f32::max);
+    Rect::new(x_min, y_min, x_max, y_max)
+}
+
+fn to_pdf_rect(rect: usvg::NonZeroRect) -> Rect {
+    Rect::new(rect.x(), rect.y(), rect.right(), rect.bottom())
+}
+
+/// Maps the unit square to the bounding box, used by `objectBoundingBox` units.
+fn bbox_transform(bbox: Rect) -> Transform {
+    Transform::from_row(
+        bbox.x2 - bbox.x1,
+        0.0,
+        0.0,
+        bbox.y2 - bbox.y1,
+        bbox.x1,
+        bbox.y1,
+    )
+}
+
+/// Fits content of the given size into the image view box.
+fn view_box_transform(view_box: &usvg::ViewBox, size: usvg::NonZeroRect) -> Transform {
+    let rect = view_box.rect;
+    // Unwrap is safe, because the view box is not empty.
+    let target = usvg::Size::from_wh(rect.width(), rect.height()).unwrap();
+    Transform::from_translate(rect.x(), rect.y()).pre_concat(usvg::utils::view_box_to_transform(
+        size,
+        view_box.aspect,
+        target,
+    ))
+}
+
+fn to_matrix(ts: Transform) -> [f32; 6] {
+    [ts.sx, ts.ky, ts.kx, ts.sy, ts.tx, ts.ty]
+}
+
+fn write_path(path: &tiny_skia::Path, content: &mut Content) {
+    let mut start = Point::zero();
+    let mut last = Point::zero();
+    for segment in path.segments() {
+        match segment {
+            PathSegment::MoveTo(p) => {
+                content.move_to(p.x, p.y);
+                start = p;
+                last = p;
+            }
+            PathSegment::LineTo(p) => {
+                content.line_to(p.x, p.y);
+                last = p;
+            }
+            PathSegment::QuadTo(p1, p) => {
+                // PDF has no quadratic curves, elevate it to a cubic one.
+                content.cubic_to(
+                    last.x + (p1.x - last.x) * 2.0 / 3.0,
+                    last.y + (p1.y - last.y) * 2.0 / 3.0,
+                    p.x + (p1.x - p.x) * 2.0 / 3.0,
+                    p.y + (p1.y - p.y) * 2.0 / 3.0,
+                    p.x,
+                    p.y,
+                );
+                last = p;
+            }
+            PathSegment::CubicTo(p1, p2, p) => {
+                content.cubic_to(p1.x, p1.y, p2.x, p2.y, p.x, p.y);
+                last = p;
+            }
+            PathSegment::Close => {
+                content.close_path();
+                last = start;
+            }
+        }
+    }
+}
+
+/// Reads the size and the number of color components from the JPEG frame header.
+fn jpeg_info(data: &[u8]) -> Option<(u32, u32, u8)> {
+    let mut i = 2;
+    while i + 9 < data.len() {
+        if data[i] != 0xFF {
+            return None;
+        }
+        let marker = data[i + 1];
+        // Fill bytes before a marker.
+        if marker == 0xFF {
+            i += 1;
+            continue;
+        }
+        // SOF0 to SOF15, except DHT, JPG and DAC.
+        if (0xC0..=0xCF).contains(&marker) && !matches!(marker, 0xC4 | 0xC8 | 0xCC) {
+            let height = u16::from_be_bytes([data[i + 5], data[i + 6]]);
+            let width = u16::from_be_bytes([data[i + 7], data[i + 8]]);
+            return Some((width as u32, height as u32, data[i + 9]));
+        }
+        let length = u16::from_be_bytes([data[i + 2], data[i + 3]]) as usize;
+        i += 2 + length;
+    }
+    None
+}
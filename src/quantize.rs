@@ -0,0 +1,222 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::collections::HashMap;
+
+/// An RGBA8 image reduced to a palette of at most 256 colors.
+pub(crate) struct Indexed {
+    /// Palette entries, ordered so that translucent entries come first, which keeps tRNS short.
+    pub palette: Vec<[u8; 4]>,
+    /// One palette index per pixel.
+    pub indices: Vec<u8>,
+}
+
+impl Indexed {
+    /// The PLTE chunk data.
+    pub fn rgb_palette(&self) -> Vec<u8> {
+        self.palette
+            .iter()
+            .flat_map(|c| [c[0], c[1], c[2]])
+            .collect()
+    }
+
+    /// The tRNS chunk data, `None` when every entry is opaque.
+    pub fn trns(&self) -> Option<Vec<u8>> {
+        let len = self.palette.iter().rposition(|c| c[3] != 255)? + 1;
+        Some(self.palette[..len].iter().map(|c| c[3]).collect())
+    }
+}
+
+/// Quantizes straight (not premultiplied) RGBA8 data.
+///
+/// Images with at most `max_colors` distinct colors keep their exact colors, others are reduced
+/// with median cut and optionally Floyd-Steinberg dithered.
+pub(crate) fn quantize(rgba: &[u8], width: usize, max_colors: usize, dither: bool) -> Indexed {
+    let max_colors = max_colors.clamp(2, 256);
+
+    let mut histogram: HashMap<[u8; 4], u32> = HashMap::new();
+    for pixel in rgba.chunks_exact(4) {
+        *histogram.entry(normalize(pixel)).or_insert(0) += 1;
+    }
+
+    let (palette, indices) = if histogram.len() <= max_colors {
+        let palette: Vec<[u8; 4]> = histogram.into_keys().collect();
+        let lookup: HashMap<[u8; 4], u8> = palette
+            .iter()
+            .enumerate()
+            .map(|(i, c)| (*c, i as u8))
+            .collect();
+        let indices = rgba
+            .chunks_exact(4)
+            .map(|pixel| lookup[&normalize(pixel)])
+            .collect();
+        (palette, indices)
+    } else {
+        let palette = median_cut(histogram.into_iter().collect(), max_colors);
+        let indices = if dither {
+            map_dithered(rgba, width, &palette)
+        } else {
+            let mut nearest = Nearest::new(&palette);
+            rgba.chunks_exact(4)
+                .map(|pixel| nearest.index_of(normalize(pixel)))
+                .collect()
+        };
+        (palette, indices)
+    };
+
+    sort_by_alpha(palette, indices)
+}
+
+/// All fully transparent pixels are the same color.
+fn normalize(pixel: &[u8]) -> [u8; 4] {
+    if pixel[3] == 0 {
+        [0, 0, 0, 0]
+    } else {
+        [pixel[0], pixel[1], pixel[2], pixel[3]]
+    }
+}
+
+fn median_cut(colors: Vec<([u8; 4], u32)>, max_colors: usize) -> Vec<[u8; 4]> {
+    let mut boxes = vec![colors];
+    while boxes.len() < max_colors {
+        // Split the box with the widest channel range.
+        let widest = boxes
+            .iter()
+            .enumerate()
+            .filter(|(_, b)| b.len() > 1)
+            .map(|(i, b)| (i, widest_channel(b)))
+            .max_by_key(|(_, (_, range))| *range);
+        let Some((i, (channel, _))) = widest else {
+            break;
+        };
+
+        let mut colors = boxes.swap_remove(i);
+        colors.sort_unstable_by_key(|(c, _)| c[channel]);
+        let total: u64 = colors.iter().map(|(_, n)| *n as u64).sum();
+        let mut count = 0;
+        let mut split = colors.len() - 1;
+        for (j, (_, n)) in colors.iter().enumerate() {
+            count += *n as u64;
+            if count * 2 >= total {
+                split = j + 1;
+                break;
+            }
+        }
+        let split = split.clamp(1, colors.len() - 1);
+        let rest = colors.split_off(split);
+        boxes.push(colors);
+        boxes.push(rest);
+    }
+
+    boxes.iter().map(|b| average(b)).collect()
+}
+
+fn widest_channel(colors: &[([u8; 4], u32)]) -> (usize, u8) {
+    (0..4)
+        .map(|channel| {
+            let (min, max) = colors.iter().fold((255, 0), |(min, max), (c, _)| {
+                (c[channel].min(min), c[channel].max(max))
+            });
+            (channel, max - min)
+        })
+        .max_by_key(|(_, range)| *range)
+        .unwrap()
+}
+
+fn average(colors: &[([u8; 4], u32)]) -> [u8; 4] {
+    let mut sum = [0u64; 4];
+    let mut total = 0u64;
+    for (c, n) in colors {
+        for channel in 0..4 {
+            sum[channel] += c[channel] as u64 * *n as u64;
+        }
+        total += *n as u64;
+    }
+    sum.map(|s| ((s + total / 2) / total) as u8)
+}
+
+/// A nearest palette entry search with a cache, since most pixels repeat.
+struct Nearest<'a> {
+    palette: &'a [[u8; 4]],
+    cache: HashMap<[u8; 4], u8>,
+}
+
+impl<'a> Nearest<'a> {
+    fn new(palette: &'a [[u8; 4]]) -> Self {
+        Nearest {
+            palette,
+            cache: HashMap::new(),
+        }
+    }
+
+    fn index_of(&mut self, color: [u8; 4]) -> u8 {
+        let palette = self.palette;
+        *self.cache.entry(color).or_insert_with(|| {
+            let distance = |c: &[u8; 4]| -> u32 {
+                (0..4)
+                    .map(|i| (c[i] as i32 - color[i] as i32).pow(2) as u32)
+                    .sum()
+            };
+            let (index, _) = palette
+                .iter()
+                .enumerate()
+                .min_by_key(|(_, c)| distance(c))
+                .unwrap();
+            index as u8
+        })
+    }
+}
+
+/// Maps pixels with Floyd-Steinberg error diffusion.
+///
+/// Fully transparent pixels are mapped exactly and do not diffuse any error.
+fn map_dithered(rgba: &[u8], width: usize, palette: &[[u8; 4]]) -> Vec<u8> {
+    let mut nearest = Nearest::new(palette);
+    let mut indices = Vec::with_capacity(rgba.len() / 4);
+    let mut errors = vec![[0i32; 4]; width + 2];
+    let mut next_errors = vec![[0i32; 4]; width + 2];
+    for row in rgba.chunks_exact(width * 4) {
+        for (x, pixel) in row.chunks_exact(4).enumerate() {
+            if pixel[3] == 0 {
+                indices.push(nearest.index_of([0, 0, 0, 0]));
+                continue;
+            }
+
+            let mut color = [0u8; 4];
+            for i in 0..4 {
+                // Errors are stored in sixteenths.
+                let value = pixel[i] as i32 + errors[x + 1][i] / 16;
+                color[i] = value.clamp(0, 255) as u8;
+            }
+            let index = nearest.index_of(color);
+            indices.push(index);
+
+            let chosen = palette[index as usize];
+            for i in 0..4 {
+                let error = color[i] as i32 - chosen[i] as i32;
+                errors[x + 2][i] += error * 7;
+                next_errors[x][i] += error * 3;
+                next_errors[x + 1][i] += error * 5;
+                next_errors[x + 2][i] += error;
+            }
+        }
+        std::mem::swap(&mut errors, &mut next_errors);
+        next_errors.iter_mut().for_each(|e| *e = [0; 4]);
+    }
+    indices
+}
+
+fn sort_by_alpha(palette: Vec<[u8; 4]>, indices: Vec<u8>) -> Indexed {
+    let mut order: Vec<usize> = (0..palette.len()).collect();
+    order.sort_by_key(|&i| (palette[i][3], palette[i]));
+    let mut remap = vec![0u8; palette.len()];
+    for (new, &old) in order.iter().enumerate() {
+        remap[old] = new as u8;
+    }
+
+    Indexed {
+        palette: order.iter().map(|&i| palette[i]).collect(),
+        indices: indices.into_iter().map(|i| remap[i as usize]).collect(),
+    }
+}
@@ -0,0 +1,679 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! The lossless VP8L bitstream, with the subtract green and predictor transforms, backward
+//! references and a color cache.
+
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+use super::tables::PLANE_TO_CODE;
+
+/// The number of length prefix codes, coded in the green alphabet after the 256 literals.
+const LENGTH_CODES: usize = 24;
+const DISTANCE_CODES: usize = 40;
+const MAX_LENGTH: usize = 4096;
+/// The largest distance of a backward reference.
+const MAX_DISTANCE: usize = (1 << 20) - 120;
+const MIN_MATCH: usize = 3;
+/// How many earlier positions with the same hash are compared.
+const MAX_CHAIN: usize = 32;
+const HASH_BITS: u32 = 18;
+/// The predictor modes are chosen per 16x16 tile.
+const PREDICTOR_BITS: u32 = 4;
+const CACHE_BITS: [u32; 4] = [0, 4, 7, 10];
+const CODE_LENGTH_ORDER: [usize; 19] = [
+    17, 18, 0, 1, 2, 3, 4, 5, 16, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15,
+];
+
+/// Encodes an ARGB image to a VP8L bitstream, the data of a `VP8L` chunk.
+pub(super) fn encode_image(argb: &[u32], width: usize, height: usize, has_alpha: bool) -> Vec<u8> {
+    let mut w = BitWriter::default();
+    w.write(0x2f, 8);
+    w.write(width as u32 - 1, 14);
+    w.write(height as u32 - 1, 14);
+    w.write(has_alpha as u32, 1);
+    w.write(0, 3);
+    write_image_stream(&mut w, argb.to_vec(), width, height, true);
+    w.finish()
+}
+
+/// Encodes the alpha channel of an ARGB image to the data of an `ALPH` chunk, compressed as a
+/// VP8L image stream of which the green channel is the alpha.
+pub(super) fn encode_alpha(argb: &[u32], width: usize, height: usize) -> Vec<u8> {
+    let green = argb.iter().map(|pixel| (pixel >> 24) << 8).collect();
+    let mut w = BitWriter::default();
+    write_image_stream(&mut w, green, width, height, false);
+    // No preprocessing nor filtering, lossless compression.
+    let mut data = vec![1];
+    data.extend(w.finish());
+    data
+}
+
+/// Writes the transforms and the entropy-coded pixels of an image.
+fn write_image_stream(
+    w: &mut BitWriter,
+    mut pixels: Vec<u32>,
+    width: usize,
+    height: usize,
+    subtract_green: bool,
+) {
+    if subtract_green {
+        w.write(1, 1);
+        w.write(2, 2);
+        for pixel in &mut pixels {
+            let green = (*pixel >> 8) & 0xff;
+            let red = ((*pixel >> 16).wrapping_sub(green)) & 0xff;
+            let blue = (*pixel).wrapping_sub(green) & 0xff;
+            *pixel = (*pixel & 0xff00ff00) | (red << 16) | blue;
+        }
+    }
+
+    let tiles_x = subsample_size(width, PREDICTOR_BITS);
+    let modes = choose_predictors(&pixels, width, height);
+    w.write(1, 1);
+    w.write(0, 2);
+    w.write(PREDICTOR_BITS - 2, 3);
+    let mode_image: Vec<u32> = modes.iter().map(|&mode| 0xff000000 | (mode << 8)).collect();
+    write_entropy_coded_image(w, &mode_image, tiles_x, false);
+    let residuals = predict(&pixels, width, height, &modes);
+
+    // No more transforms.
+    w.write(0, 1);
+    write_entropy_coded_image(w, &residuals, width, true);
+}
+
+fn subsample_size(size: usize, bits: u32) -> usize {
+    (size + (1 << bits) - 1) >> bits
+}
+
+/// Writes the color cache, the prefix codes and the pixels of an image, `is_main` tells the
+/// image of the picture from the images of the transforms.
+fn write_entropy_coded_image(w: &mut BitWriter, pixels: &[u32], width: usize, is_main: bool) {
+    let refs = backward_references(pixels, width);
+    let (cache_bits, symbols, histograms) = CACHE_BITS
+        .iter()
+        .map(|&cache_bits| {
+            let (symbols, histograms) = to_symbols(pixels, &refs, width, cache_bits);
+            (cache_bits, symbols, histograms)
+        })
+        .min_by_key(|(_, _, histograms)| estimate_bits(histograms))
+        .unwrap();
+
+    if cache_bits > 0 {
+        w.write(1, 1);
+        w.write(cache_bits, 4);
+    } else {
+        w.write(0, 1);
+    }
+    if is_main {
+        // A single group of prefix codes for the whole image.
+        w.write(0, 1);
+    }
+    let codes: Vec<PrefixCode> = histograms
+        .iter()
+        .map(|histogram| PrefixCode::new(histogram, 15))
+        .collect();
+    for code in &codes {
+        code.write_header(w);
+    }
+    for symbol in symbols {
+        match symbol {
+            Symbol::Literal(argb) => {
+                codes[0].write_symbol(w, ((argb >> 8) & 0xff) as usize);
+                codes[1].write_symbol(w, ((argb >> 16) & 0xff) as usize);
+                codes[2].write_symbol(w, (argb & 0xff) as usize);
+                codes[3].write_symbol(w, (argb >> 24) as usize);
+            }
+            Symbol::CacheIndex(index) => {
+                codes[0].write_symbol(w, 256 + LENGTH_CODES + index);
+            }
+            Symbol::Copy { length, distance } => {
+                let (code, extra_bits, extra) = prefix_encode(length);
+                codes[0].write_symbol(w, 256 + code);
+                w.write(extra, extra_bits);
+                let (code, extra_bits, extra) = prefix_encode(distance);
+                codes[4].write_symbol(w, code);
+                w.write(extra, extra_bits);
+            }
+        }
+    }
+}
+
+/// A backward reference, or a literal pixel when `length` is 0.
+#[derive(Clone, Copy)]
+struct Reference {
+    length: usize,
+    distance: usize,
+}
+
+/// Finds the backward references with a hash chain, greedily.
+fn backward_references(pixels: &[u32], width: usize) -> Vec<Reference> {
+    let n = pixels.len();
+    let mut head = vec![u32::MAX; 1 << HASH_BITS];
+    let mut chain = vec![u32::MAX; n.min(1 << 20)];
+    let chain_mask = chain.len() - 1;
+    let hash = |i: usize| -> usize {
+        let key = pixels[i]
+            .wrapping_mul(0x1e35a7bd)
+            .wrapping_add(pixels[i + 1].wrapping_mul(0x9e3779b1));
+        (key >> (32 - HASH_BITS)) as usize
+    };
+    let match_length = |from: usize, to: usize| -> usize {
+        let max = (n - to).min(MAX_LENGTH);
+        (0..max)
+            .find(|&k| pixels[from + k] != pixels[to + k])
+            .unwrap_or(max)
+    };
+
+    let mut refs = Vec::new();
+    let mut i = 0;
+    while i < n {
+        let mut best = Reference {
+            length: 0,
+            distance: 0,
+        };
+        if i + 1 < n {
+            // The previous pixel and the pixel above are the likeliest matches.
+            for distance in [1, width] {
+                if distance <= i {
+                    let length = match_length(i - distance, i);
+                    if length > best.length {
+                        best = Reference { length, distance };
+                    }
+                }
+            }
+            let max_length = (n - i).min(MAX_LENGTH);
+            let mut candidate = head[hash(i)];
+            for _ in 0..MAX_CHAIN {
+                if best.length == max_length {
+                    break;
+                }
+                let j = candidate as usize;
+                if candidate == u32::MAX || j >= i || i - j > MAX_DISTANCE {
+                    break;
+                }
+                let length = match_length(j, i);
+                if length > best.length {
+                    best = Reference {
+                        length,
+                        distance: i - j,
+                    };
+                }
+                candidate = chain[j & chain_mask];
+            }
+        }
+        let step = if best.length >= MIN_MATCH {
+            refs.push(best);
+            best.length
+        } else {
+            refs.push(Reference {
+                length: 0,
+                distance: 0,
+            });
+            1
+        };
+        for k in i..(i + step).min(n - 1) {
+            let h = hash(k);
+            chain[k & chain_mask] = head[h];
+            head[h] = k as u32;
+        }
+        i += step;
+    }
+    refs
+}
+
+enum Symbol {
+    Literal(u32),
+    CacheIndex(usize),
+    Copy { length: usize, distance: usize },
+}
+
+/// Converts the references to symbols with a color cache of `cache_bits`, and counts the
+/// symbols of the green, red, blue, alpha and distance alphabets.
+fn to_symbols(
+    pixels: &[u32],
+    refs: &[Reference],
+    width: usize,
+    cache_bits: u32,
+) -> (Vec<Symbol>, [Vec<u32>; 5]) {
+    let cache_size = if cache_bits > 0 { 1 << cache_bits } else { 0 };
+    let mut cache = vec![0u32; cache_size];
+    let cache_index = |argb: u32| (argb.wrapping_mul(0x1e35a7bd) >> (32 - cache_bits)) as usize;
+    let mut histograms = [
+        vec![0u32; 256 + LENGTH_CODES + cache_size],
+        vec![0u32; 256],
+        vec![0u32; 256],
+        vec![0u32; 256],
+        vec![0u32; DISTANCE_CODES],
+    ];
+
+    let mut symbols = Vec::with_capacity(refs.len());
+    let mut i = 0;
+    for reference in refs {
+        if reference.length == 0 {
+            let argb = pixels[i];
+            if cache_size > 0 && cache[cache_index(argb)] == argb {
+                let index = cache_index(argb);
+                histograms[0][256 + LENGTH_CODES + index] += 1;
+                symbols.push(Symbol::CacheIndex(index));
+            } else {
+                histograms[0][((argb >> 8) & 0xff) as usize] += 1;
+                histograms[1][((argb >> 16) & 0xff) as usize] += 1;
+                histograms[2][(argb & 0xff) as usize] += 1;
+                histograms[3][(argb >> 24) as usize] += 1;
+                symbols.push(Symbol::Literal(argb));
+            }
+            if cache_size > 0 {
+                cache[cache_index(argb)] = argb;
+            }
+            i += 1;
+        } else {
+            let distance = distance_to_plane_code(width, reference.distance);
+            histograms[0][256 + prefix_encode(reference.length).0] += 1;
+            histograms[4][prefix_encode(distance).0] += 1;
+            symbols.push(Symbol::Copy {
+                length: reference.length,
+                distance,
+            });
+            if cache_size > 0 {
+                for &argb in &pixels[i..i + reference.length] {
+                    cache[cache_index(argb)] = argb;
+                }
+            }
+            i += reference.length;
+        }
+    }
+    (symbols, histograms)
+}
+
+/// The entropy of the symbols, an estimate of their size once coded.
+fn estimate_bits(histograms: &[Vec<u32>; 5]) -> u64 {
+    let mut bits = 0.0;
+    for histogram in histograms {
+        let total: u32 = histogram.iter().sum();
+        for &count in histogram.iter().filter(|&&count| count > 0) {
+            bits += count as f64 * (total as f64 / count as f64).log2();
+        }
+        // The prefix code itself.
+        bits += histogram.iter().filter(|&&count| count > 0).count() as f64 * 4.0;
+    }
+    bits as u64
+}
+
+/// Maps a distance to the distance codes, of which the first 120 are the pixels close in 2D.
+fn distance_to_plane_code(width: usize, distance: usize) -> usize {
+    let y_offset = distance / width;
+    let x_offset = distance - y_offset * width;
+    if x_offset <= 8 && y_offset < 8 {
+        PLANE_TO_CODE[y_offset * 16 + 8 - x_offset] as usize + 1
+    } else if x_offset + 8 > width && y_offset < 7 {
+        PLANE_TO_CODE[(y_offset + 1) * 16 + 8 + (width - x_offset)] as usize + 1
+    } else {
+        distance + 120
+    }
+}
+
+/// Splits a length or a distance code into its prefix code, extra bit count and extra bits.
+fn prefix_encode(value: usize) -> (usize, u32, u32) {
+    let n = value - 1;
+    if n < 4 {
+        return (n, 0, 0);
+    }
+    let highest_bit = usize::BITS - 1 - n.leading_zeros();
+    let second_bit = (n >> (highest_bit - 1)) & 1;
+    let extra_bits = highest_bit - 1;
+    let offset = (2 + second_bit) << extra_bits;
+    (
+        2 * highest_bit as usize + second_bit,
+        extra_bits,
+        (n - offset) as u32,
+    )
+}
+
+/// Chooses the predictor mode of each tile, the one with the smallest residuals.
+fn choose_predictors(pixels: &[u32], width: usize, height: usize) -> Vec<u32> {
+    let tiles_x = subsample_size(width, PREDICTOR_BITS);
+    let tiles_y = subsample_size(height, PREDICTOR_BITS);
+    let tile_size = 1 << PREDICTOR_BITS;
+    let mut modes = Vec::with_capacity(tiles_x * tiles_y);
+    for tile_y in 0..tiles_y {
+        for tile_x in 0..tiles_x {
+            let best = (0..14)
+                .min_by_key(|&mode| {
+                    let mut cost = 0u64;
+                    for y in
+                        (tile_y * tile_size..(tile_y + 1) * tile_size).take_while(|&y| y < height)
+                    {
+                        for x in (tile_x * tile_size..(tile_x + 1) * tile_size)
+                            .take_while(|&x| x < width)
+                        {
+                            let i = y * width + x;
+                            let residual = sub_pixels(pixels[i], predictor(pixels, width, i, mode));
+                            cost += residual
+                                .to_le_bytes()
+                                .iter()
+                                .map(|&c| (c as i8).unsigned_abs() as u64)
+                                .sum::<u64>();
+                        }
+                    }
+                    cost
+                })
+                .unwrap();
+            modes.push(best);
+        }
+    }
+    modes
+}
+
+/// Replaces the pixels with their residuals from the predictor of their tile.
+fn predict(pixels: &[u32], width: usize, height: usize, modes: &[u32]) -> Vec<u32> {
+    let tiles_x = subsample_size(width, PREDICTOR_BITS);
+    let mut residuals = Vec::with_capacity(pixels.len());
+    for y in 0..height {
+        for x in 0..width {
+            let i = y * width + x;
+            let mode = modes[(y >> PREDICTOR_BITS) * tiles_x + (x >> PREDICTOR_BITS)];
+            residuals.push(sub_pixels(pixels[i], predictor(pixels, width, i, mode)));
+        }
+    }
+    residuals
+}
+
+/// Predicts the pixel `i` from its decoded neighbors, the first row and column use fixed modes.
+fn predictor(pixels: &[u32], width: usize, i: usize, mode: u32) -> u32 {
+    let (x, y) = (i % width, i / width);
+    if y == 0 {
+        return if x == 0 { 0xff000000 } else { pixels[i - 1] };
+    }
+    if x == 0 {
+        return pixels[i - width];
+    }
+    let left = pixels[i - 1];
+    let top = pixels[i - width];
+    let top_left = pixels[i - width - 1];
+    // The top-right of the last column is the first pixel of the current row.
+    let top_right = pixels[i - width + 1];
+    match mode {
+        0 => 0xff000000,
+        1 => left,
+        2 => top,
+        3 => top_right,
+        4 => top_left,
+        5 => average2(average2(left, top_right), top),
+        6 => average2(left, top_left),
+        7 => average2(left, top),
+        8 => average2(top_left, top),
+        9 => average2(top, top_right),
+        10 => average2(average2(left, top_left), average2(top, top_right)),
+        11 => select(top, left, top_left),
+        12 => map_channels(|c| {
+            (channel(left, c) + channel(top, c) - channel(top_left, c)).clamp(0, 255)
+        }),
+        _ => {
+            let average = average2(left, top);
+            map_channels(|c| {
+                let a = channel(average, c);
+                (a + (a - channel(top_left, c)) / 2).clamp(0, 255)
+            })
+        }
+    }
+}
+
+fn channel(argb: u32, c: u32) -> i32 {
+    ((argb >> (c * 8)) & 0xff) as i32
+}
+
+fn map_channels(f: impl Fn(u32) -> i32) -> u32 {
+    (0..4).fold(0, |argb, c| argb | ((f(c) as u32) << (c * 8)))
+}
+
+fn average2(a: u32, b: u32) -> u32 {
+    (((a ^ b) & 0xfefefefe) >> 1) + (a & b)
+}
+
+fn select(top: u32, left: u32, top_left: u32) -> u32 {
+    let distance: i32 = (0..4)
+        .map(|c| {
+            (channel(left, c) - channel(top_left, c)).abs()
+                - (channel(top, c) - channel(top_left, c)).abs()
+        })
+        .sum();
+    if distance <= 0 {
+        top
+    } else {
+        left
+    }
+}
+
+fn sub_pixels(a: u32, b: u32) -> u32 {
+    let alpha_and_green = 0x00ff00ff_u32
+        .wrapping_add(a & 0xff00ff00)
+        .wrapping_sub(b & 0xff00ff00);
+    let red_and_blue = 0xff00ff00_u32
+        .wrapping_add(a & 0x00ff00ff)
+        .wrapping_sub(b & 0x00ff00ff);
+    (alpha_and_green & 0xff00ff00) | (red_and_blue & 0x00ff00ff)
+}
+
+/// A canonical prefix code.
+struct PrefixCode {
+    lengths: Vec<u8>,
+    codes: Vec<u16>,
+    /// A code with a single symbol writes no bits.
+    is_single: bool,
+}
+
+impl PrefixCode {
+    fn new(histogram: &[u32], max_length: u8) -> PrefixCode {
+        let lengths = code_lengths(histogram, max_length);
+        let codes = canonical_codes(&lengths);
+        let is_single = lengths.iter().filter(|&&length| length > 0).count() <= 1;
+        PrefixCode {
+            lengths,
+            codes,
+            is_single,
+        }
+    }
+
+    fn write_symbol(&self, w: &mut BitWriter, symbol: usize) {
+        if !self.is_single {
+            w.write(self.codes[symbol] as u32, self.lengths[symbol] as u32);
+        }
+    }
+
+    fn write_header(&self, w: &mut BitWriter) {
+        let symbols: Vec<usize> = (0..self.lengths.len())
+            .filter(|&symbol| self.lengths[symbol] > 0)
+            .collect();
+        if symbols.len() <= 2 && symbols.iter().all(|&symbol| symbol < 256) {
+            // A simple code of one or two 8-bit symbols.
+            let first = symbols.first().copied().unwrap_or(0);
+            w.write(1, 1);
+            w.write(symbols.len().saturating_sub(1) as u32, 1);
+            if first < 2 {
+                w.write(0, 1);
+                w.write(first as u32, 1);
+            } else {
+                w.write(1, 1);
+                w.write(first as u32, 8);
+            }
+            if let Some(&second) = symbols.get(1) {
+                w.write(second as u32, 8);
+            }
+            return;
+        }
+
+        w.write(0, 1);
+        let tokens = code_length_tokens(&self.lengths);
+        let mut histogram = [0u32; 19];
+        for &(token, _) in &tokens {
+            histogram[token as usize] += 1;
+        }
+        let code_length_code = PrefixCode::new(&histogram, 7);
+        let count = (4..=19)
+            .rev()
+            .find(|&count| code_length_code.lengths[CODE_LENGTH_ORDER[count - 1]] > 0)
+            .unwrap_or(4);
+        w.write(count as u32 - 4, 4);
+        for &symbol in &CODE_LENGTH_ORDER[..count] {
+            w.write(code_length_code.lengths[symbol] as u32, 3);
+        }
+        // The code lengths of the whole alphabet follow.
+        w.write(0, 1);
+        for (token, extra) in tokens {
+            code_length_code.write_symbol(w, token as usize);
+            match token {
+                16 => w.write(extra as u32, 2),
+                17 => w.write(extra as u32, 3),
+                18 => w.write(extra as u32, 7),
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Run-length encodes the code lengths, with the repeat codes 16 (the previous non-zero
+/// length), 17 and 18 (zeros).
+fn code_length_tokens(lengths: &[u8]) -> Vec<(u8, u8)> {
+    let mut tokens = Vec::new();
+    let mut previous = 8;
+    let mut i = 0;
+    while i < lengths.len() {
+        let length = lengths[i];
+        let mut run = lengths[i..].iter().take_while(|&&l| l == length).count();
+        i += run;
+        if length == 0 {
+            while run >= 11 {
+                let repeat = run.min(138);
+                tokens.push((18, (repeat - 11) as u8));
+                run -= repeat;
+            }
+            if run >= 3 {
+                tokens.push((17, (run - 3) as u8));
+                run = 0;
+            }
+        } else {
+            if length != previous {
+                tokens.push((length, 0));
+                previous = length;
+                run -= 1;
+            }
+            while run >= 3 {
+                let repeat = run.min(6);
+                tokens.push((16, (repeat - 3) as u8));
+                run -= repeat;
+            }
+        }
+        tokens.extend(std::iter::repeat((length, 0)).take(run));
+    }
+    tokens
+}
+
+/// Computes the lengths of a Huffman code limited to `max_length`, by flattening the counts
+/// until the tree is shallow enough.
+fn code_lengths(histogram: &[u32], max_length: u8) -> Vec<u8> {
+    let mut lengths = vec![0; histogram.len()];
+    let symbols: Vec<usize> = (0..histogram.len())
+        .filter(|&symbol| histogram[symbol] > 0)
+        .collect();
+    if symbols.len() == 1 {
+        lengths[symbols[0]] = 1;
+    }
+    if symbols.len() <= 1 {
+        return lengths;
+    }
+
+    let mut min_count = 1;
+    loop {
+        let mut weights: Vec<u64> = symbols
+            .iter()
+            .map(|&symbol| histogram[symbol].max(min_count) as u64)
+            .collect();
+        let mut parents = vec![usize::MAX; symbols.len()];
+        let mut heap: BinaryHeap<Reverse<(u64, usize)>> = weights
+            .iter()
+            .enumerate()
+            .map(|(node, &weight)| Reverse((weight, node)))
+            .collect();
+        while heap.len() > 1 {
+            let Reverse((weight_a, a)) = heap.pop().unwrap();
+            let Reverse((weight_b, b)) = heap.pop().unwrap();
+            let node = weights.len();
+            weights.push(weight_a + weight_b);
+            parents.push(usize::MAX);
+            parents[a] = node;
+            parents[b] = node;
+            heap.push(Reverse((weight_a + weight_b, node)));
+        }
+        // The parents come after their children, the root is the last node.
+        let mut depths = vec![0u8; weights.len()];
+        for node in (0..weights.len() - 1).rev() {
+            depths[node] = depths[parents[node]] + 1;
+        }
+        if depths[..symbols.len()]
+            .iter()
+            .all(|&depth| depth <= max_length)
+        {
+            for (leaf, &symbol) in symbols.iter().enumerate() {
+                lengths[symbol] = depths[leaf];
+            }
+            return lengths;
+        }
+        min_count *= 2;
+    }
+}
+
+/// Assigns the canonical codes of the lengths, bit-reversed as the codes are read from the
+/// least significant bit.
+fn canonical_codes(lengths: &[u8]) -> Vec<u16> {
+    let mut counts = [0u16; 16];
+    for &length in lengths.iter().filter(|&&length| length > 0) {
+        counts[length as usize] += 1;
+    }
+    let mut next_code = [0u16; 16];
+    let mut code = 0;
+    for length in 1..16 {
+        code = (code + counts[length - 1]) << 1;
+        next_code[length] = code;
+    }
+    lengths
+        .iter()
+        .map(|&length| {
+            if length == 0 {
+                return 0;
+            }
+            let code = next_code[length as usize];
+            next_code[length as usize] += 1;
+            code.reverse_bits() >> (16 - length)
+        })
+        .collect()
+}
+
+/// Writes the bits from the least significant bit of each byte.
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    bits: u64,
+    count: u32,
+}
+
+impl BitWriter {
+    fn write(&mut self, value: u32, count: u32) {
+        self.bits |= (value as u64) << self.count;
+        self.count += count;
+        while self.count >= 8 {
+            self.bytes.push(self.bits as u8);
+            self.bits >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.count > 0 {
+            self.bytes.push(self.bits as u8);
+        }
+        self.bytes
+    }
+}
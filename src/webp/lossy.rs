@@ -0,0 +1,840 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! The lossy VP8 key frame (RFC 6386), with 16x16 luma and 8x8 chroma intra prediction.
+
+use super::tables::{
+    AC_TABLE, BANDS, CAT_PROBAS, COEFFS_PROBA0, COEFFS_UPDATE_PROBA, DC_TABLE, ZIGZAG,
+};
+
+type Probas = [[[[u8; 11]; 3]; 8]; 4];
+
+/// The block types of the coefficient probabilities.
+const TYPE_Y_AFTER_Y2: usize = 0;
+const TYPE_Y2: usize = 1;
+const TYPE_UV: usize = 2;
+
+const MAX_LEVEL: i32 = 2047;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Mode {
+    Dc,
+    Vertical,
+    Horizontal,
+    TrueMotion,
+}
+
+const MODES: [Mode; 4] = [Mode::Dc, Mode::Vertical, Mode::Horizontal, Mode::TrueMotion];
+
+/// The dequantization factors of the DC and the AC coefficients.
+struct Quantizer {
+    index: usize,
+    y1: [i32; 2],
+    y2: [i32; 2],
+    uv: [i32; 2],
+}
+
+impl Quantizer {
+    fn new(index: usize) -> Quantizer {
+        Quantizer {
+            index,
+            y1: [DC_TABLE[index] as i32, AC_TABLE[index] as i32],
+            y2: [
+                DC_TABLE[index] as i32 * 2,
+                ((AC_TABLE[index] as i32 * 101581) >> 16).max(8),
+            ],
+            uv: [DC_TABLE[index.min(117)] as i32, AC_TABLE[index] as i32],
+        }
+    }
+}
+
+/// The quantized coefficients of a macroblock, in the natural order.
+struct Macroblock {
+    luma_mode: Mode,
+    chroma_mode: Mode,
+    y2: [i32; 16],
+    y: [[i32; 16]; 16],
+    /// The four U blocks, then the four V blocks.
+    uv: [[i32; 16]; 8],
+}
+
+/// Encodes an ARGB image to a VP8 key frame, the data of a `VP8 ` chunk. `quality` goes from
+/// 0 to 100.
+pub(super) fn encode_image(argb: &[u32], width: usize, height: usize, quality: f32) -> Vec<u8> {
+    let mb_width = (width + 15) / 16;
+    let mb_height = (height + 15) / 16;
+    let planes = Planes::new(argb, width, height, mb_width, mb_height);
+    let quantizer = Quantizer::new(quantizer_index(quality));
+
+    let mut reconstructed = Planes {
+        y: vec![0; planes.y.len()],
+        u: vec![0; planes.u.len()],
+        v: vec![0; planes.v.len()],
+        stride: planes.stride,
+    };
+    let mut macroblocks = Vec::with_capacity(mb_width * mb_height);
+    for mb_y in 0..mb_height {
+        for mb_x in 0..mb_width {
+            macroblocks.push(encode_macroblock(
+                &planes,
+                &mut reconstructed,
+                &quantizer,
+                mb_x,
+                mb_y,
+            ));
+        }
+    }
+
+    // Counts the coefficient bits first, to update the probabilities that save bits.
+    let mut stats = Stats {
+        counts: [[[[[0; 2]; 11]; 3]; 8]; 4],
+    };
+    write_tokens(&mut stats, &macroblocks, mb_width);
+    let probas = stats.probas();
+
+    let mut header = BoolWriter::new();
+    write_frame_header(&mut header, &probas, quantizer.index);
+    for mb in &macroblocks {
+        write_modes(&mut header, mb);
+    }
+    let first_partition = header.finish();
+
+    let mut tokens = TokenWriter {
+        writer: BoolWriter::new(),
+        probas: &probas,
+    };
+    write_tokens(&mut tokens, &macroblocks, mb_width);
+    let tokens = tokens.writer.finish();
+
+    let mut data = Vec::with_capacity(10 + first_partition.len() + tokens.len());
+    // A key frame, version 0, shown.
+    let tag = ((first_partition.len() as u32) << 5) | (1 << 4);
+    data.extend_from_slice(&tag.to_le_bytes()[..3]);
+    data.extend_from_slice(&[0x9d, 0x01, 0x2a]);
+    data.extend_from_slice(&(width as u16).to_le_bytes());
+    data.extend_from_slice(&(height as u16).to_le_bytes());
+    data.extend(first_partition);
+    data.extend(tokens);
+    data
+}
+
+/// Maps the quality to the quantizer index like libwebp does.
+fn quantizer_index(quality: f32) -> usize {
+    let c = quality as f64 / 100.0;
+    let linear = if c < 0.75 {
+        c * 2.0 / 3.0
+    } else {
+        2.0 * c - 1.0
+    };
+    (127.0 * (1.0 - linear.cbrt())).clamp(0.0, 127.0) as usize
+}
+
+/// The Y, U and V planes, padded to whole macroblocks.
+struct Planes {
+    y: Vec<u8>,
+    u: Vec<u8>,
+    v: Vec<u8>,
+    /// The stride of the luma plane, twice the chroma stride.
+    stride: usize,
+}
+
+impl Planes {
+    fn new(argb: &[u32], width: usize, height: usize, mb_width: usize, mb_height: usize) -> Planes {
+        let stride = mb_width * 16;
+        let rows = mb_height * 16;
+        // The padding repeats the last column and row.
+        let rgb = |x: usize, y: usize| -> [i32; 3] {
+            let pixel = argb[y.min(height - 1) * width + x.min(width - 1)];
+            [
+                ((pixel >> 16) & 0xff) as i32,
+                ((pixel >> 8) & 0xff) as i32,
+                (pixel & 0xff) as i32,
+            ]
+        };
+        let mut y_plane = Vec::with_capacity(stride * rows);
+        for y in 0..rows {
+            for x in 0..stride {
+                let [r, g, b] = rgb(x, y);
+                y_plane.push(
+                    ((16839 * r + 33059 * g + 6420 * b + (1 << 15) + (16 << 16)) >> 16) as u8,
+                );
+            }
+        }
+        let mut u_plane = Vec::with_capacity(stride * rows / 4);
+        let mut v_plane = Vec::with_capacity(stride * rows / 4);
+        for y in (0..rows).step_by(2) {
+            for x in (0..stride).step_by(2) {
+                let mut sum = [0; 3];
+                for (dx, dy) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+                    let rgb = rgb(x + dx, y + dy);
+                    for c in 0..3 {
+                        sum[c] += rgb[c];
+                    }
+                }
+                let [r, g, b] = sum;
+                u_plane.push(clip_uv(-9719 * r - 19081 * g + 28800 * b));
+                v_plane.push(clip_uv(28800 * r - 24116 * g - 4684 * b));
+            }
+        }
+        Planes {
+            y: y_plane,
+            u: u_plane,
+            v: v_plane,
+            stride,
+        }
+    }
+}
+
+fn clip_uv(uv: i32) -> u8 {
+    ((uv + (1 << 17) + (128 << 18)) >> 18).clamp(0, 255) as u8
+}
+
+/// The samples around a block the decoder predicts it from.
+struct Borders {
+    top: Vec<i32>,
+    left: Vec<i32>,
+    top_left: i32,
+    has_top: bool,
+    has_left: bool,
+}
+
+impl Borders {
+    fn new(plane: &[u8], stride: usize, x: usize, y: usize, size: usize) -> Borders {
+        let at = |x: usize, y: usize| plane[y * stride + x] as i32;
+        Borders {
+            top: (0..size)
+                .map(|i| if y > 0 { at(x + i, y - 1) } else { 127 })
+                .collect(),
+            left: (0..size)
+                .map(|i| if x > 0 { at(x - 1, y + i) } else { 129 })
+                .collect(),
+            top_left: match (x, y) {
+                (_, 0) => 127,
+                (0, _) => 129,
+                _ => at(x - 1, y - 1),
+            },
+            has_top: y > 0,
+            has_left: x > 0,
+        }
+    }
+
+    fn predict(&self, mode: Mode) -> Vec<i32> {
+        let size = self.top.len();
+        let shift = size.trailing_zeros();
+        let dc = match (self.has_top, self.has_left) {
+            (true, true) => {
+                let sum: i32 = self.top.iter().chain(&self.left).sum();
+                (sum + size as i32) >> (shift + 1)
+            }
+            (true, false) => (self.top.iter().sum::<i32>() + (size as i32 >> 1)) >> shift,
+            (false, true) => (self.left.iter().sum::<i32>() + (size as i32 >> 1)) >> shift,
+            (false, false) => 128,
+        };
+        let mut prediction = Vec::with_capacity(size * size);
+        for y in 0..size {
+            for x in 0..size {
+                prediction.push(match mode {
+                    Mode::Dc => dc,
+                    Mode::Vertical => self.top[x],
+                    Mode::Horizontal => self.left[y],
+                    Mode::TrueMotion => (self.top[x] + self.left[y] - self.top_left).clamp(0, 255),
+                });
+            }
+        }
+        prediction
+    }
+}
+
+/// Chooses the mode with the smallest prediction error over one or more planes.
+fn choose_mode(sources: &[(&[u8], &Borders)], stride: usize, x: usize, y: usize) -> Mode {
+    *MODES
+        .iter()
+        .min_by_key(|&&mode| {
+            sources
+                .iter()
+                .map(|(plane, borders)| {
+                    let size = borders.top.len();
+                    let prediction = borders.predict(mode);
+                    let mut error = 0;
+                    for j in 0..size {
+                        for i in 0..size {
+                            let diff =
+                                plane[(y + j) * stride + x + i] as i32 - prediction[j * size + i];
+                            error += diff * diff;
+                        }
+                    }
+                    error
+                })
+                .sum::<i32>()
+        })
+        .unwrap()
+}
+
+fn encode_macroblock(
+    planes: &Planes,
+    reconstructed: &mut Planes,
+    quantizer: &Quantizer,
+    mb_x: usize,
+    mb_y: usize,
+) -> Macroblock {
+    let stride = planes.stride;
+    let (x, y) = (mb_x * 16, mb_y * 16);
+    let borders = Borders::new(&reconstructed.y, stride, x, y, 16);
+    let luma_mode = choose_mode(&[(&planes.y, &borders)], stride, x, y);
+    let prediction = borders.predict(luma_mode);
+
+    // The DC coefficients of the 16 blocks go to the Y2 block through a Walsh-Hadamard
+    // transform.
+    let mut coefficients = [[0; 16]; 16];
+    for (n, block) in coefficients.iter_mut().enumerate() {
+        let (bx, by) = (n % 4 * 4, n / 4 * 4);
+        *block = forward_transform(|i, j| {
+            planes.y[(y + by + j) * stride + x + bx + i] as i32 - prediction[(by + j) * 16 + bx + i]
+        });
+    }
+    let mut dc = [0; 16];
+    for n in 0..16 {
+        dc[n] = coefficients[n][0];
+    }
+    let y2 = quantize(&forward_wht(&dc), quantizer.y2);
+    let mut y_levels = [[0; 16]; 16];
+    for n in 0..16 {
+        y_levels[n] = quantize(&coefficients[n], quantizer.y1);
+        y_levels[n][0] = 0;
+    }
+
+    // Reconstructs the macroblock as the decoder does.
+    let dequantized_y2 = dequantize(&y2, quantizer.y2);
+    let last = (0..16).rev().find(|&n| y2[ZIGZAG[n]] != 0);
+    let dc = if last.unwrap_or(0) > 0 {
+        inverse_wht(&dequantized_y2)
+    } else {
+        [(dequantized_y2[0] + 3) >> 3; 16]
+    };
+    for n in 0..16 {
+        let (bx, by) = (n % 4 * 4, n / 4 * 4);
+        let mut block = dequantize(&y_levels[n], quantizer.y1);
+        block[0] = dc[n];
+        let pixels = inverse_transform(&block, |i, j| prediction[(by + j) * 16 + bx + i]);
+        for j in 0..4 {
+            for i in 0..4 {
+                reconstructed.y[(y + by + j) * stride + x + bx + i] = pixels[j * 4 + i];
+            }
+        }
+    }
+
+    let stride = stride / 2;
+    let (x, y) = (mb_x * 8, mb_y * 8);
+    let u_borders = Borders::new(&reconstructed.u, stride, x, y, 8);
+    let v_borders = Borders::new(&reconstructed.v, stride, x, y, 8);
+    let chroma_mode = choose_mode(
+        &[(&planes.u, &u_borders), (&planes.v, &v_borders)],
+        stride,
+        x,
+        y,
+    );
+    let mut uv = [[0; 16]; 8];
+    let (u_blocks, v_blocks) = uv.split_at_mut(4);
+    for (plane, target, borders, blocks) in [
+        (&planes.u, &mut reconstructed.u, &u_borders, u_blocks),
+        (&planes.v, &mut reconstructed.v, &v_borders, v_blocks),
+    ] {
+        let prediction = borders.predict(chroma_mode);
+        for (n, levels) in blocks.iter_mut().enumerate() {
+            let (bx, by) = (n % 2 * 4, n / 2 * 4);
+            let block = forward_transform(|i, j| {
+                plane[(y + by + j) * stride + x + bx + i] as i32 - prediction[(by + j) * 8 + bx + i]
+            });
+            *levels = quantize(&block, quantizer.uv);
+            let pixels = inverse_transform(&dequantize(levels, quantizer.uv), |i, j| {
+                prediction[(by + j) * 8 + bx + i]
+            });
+            for j in 0..4 {
+                for i in 0..4 {
+                    target[(y + by + j) * stride + x + bx + i] = pixels[j * 4 + i];
+                }
+            }
+        }
+    }
+
+    Macroblock {
+        luma_mode,
+        chroma_mode,
+        y2,
+        y: y_levels,
+        uv,
+    }
+}
+
+/// Quantizes the coefficients, with a rounding a bit under a half for the coefficients close
+/// to zero to become zero.
+fn quantize(coefficients: &[i32; 16], factors: [i32; 2]) -> [i32; 16] {
+    let mut levels = [0; 16];
+    for i in 0..16 {
+        let (factor, bias) = if i == 0 {
+            (factors[0], 96)
+        } else {
+            (factors[1], 110)
+        };
+        let level = ((coefficients[i].abs() << 8) + bias * factor) / (factor << 8);
+        levels[i] = level.min(MAX_LEVEL) * coefficients[i].signum();
+    }
+    levels
+}
+
+fn dequantize(levels: &[i32; 16], factors: [i32; 2]) -> [i32; 16] {
+    let mut coefficients = [0; 16];
+    for i in 0..16 {
+        coefficients[i] = levels[i] * factors[(i > 0) as usize];
+    }
+    coefficients
+}
+
+/// The forward DCT of a 4x4 block of residuals, as libwebp computes it.
+fn forward_transform(residual: impl Fn(usize, usize) -> i32) -> [i32; 16] {
+    let mut tmp = [0; 16];
+    for j in 0..4 {
+        let d = [
+            residual(0, j),
+            residual(1, j),
+            residual(2, j),
+            residual(3, j),
+        ];
+        let a0 = d[0] + d[3];
+        let a1 = d[1] + d[2];
+        let a2 = d[1] - d[2];
+        let a3 = d[0] - d[3];
+        tmp[j * 4] = (a0 + a1) * 8;
+        tmp[1 + j * 4] = (a2 * 2217 + a3 * 5352 + 1812) >> 9;
+        tmp[2 + j * 4] = (a0 - a1) * 8;
+        tmp[3 + j * 4] = (a3 * 2217 - a2 * 5352 + 937) >> 9;
+    }
+    let mut out = [0; 16];
+    for i in 0..4 {
+        let a0 = tmp[i] + tmp[12 + i];
+        let a1 = tmp[4 + i] + tmp[8 + i];
+        let a2 = tmp[4 + i] - tmp[8 + i];
+        let a3 = tmp[i] - tmp[12 + i];
+        out[i] = (a0 + a1 + 7) >> 4;
+        out[4 + i] = ((a2 * 2217 + a3 * 5352 + 12000) >> 16) + (a3 != 0) as i32;
+        out[8 + i] = (a0 - a1 + 7) >> 4;
+        out[12 + i] = (a3 * 2217 - a2 * 5352 + 51000) >> 16;
+    }
+    out
+}
+
+/// The inverse DCT of a 4x4 block, added to the prediction.
+fn inverse_transform(
+    coefficients: &[i32; 16],
+    prediction: impl Fn(usize, usize) -> i32,
+) -> [u8; 16] {
+    let mul1 = |a: i32| ((a * 20091) >> 16) + a;
+    let mul2 = |a: i32| (a * 35468) >> 16;
+    let mut tmp = [0; 16];
+    for i in 0..4 {
+        let a = coefficients[i] + coefficients[8 + i];
+        let b = coefficients[i] - coefficients[8 + i];
+        let c = mul2(coefficients[4 + i]) - mul1(coefficients[12 + i]);
+        let d = mul1(coefficients[4 + i]) + mul2(coefficients[12 + i]);
+        tmp[i * 4] = a + d;
+        tmp[i * 4 + 1] = b + c;
+        tmp[i * 4 + 2] = b - c;
+        tmp[i * 4 + 3] = a - d;
+    }
+    let mut pixels = [0; 16];
+    for j in 0..4 {
+        let dc = tmp[j] + 4;
+        let a = dc + tmp[8 + j];
+        let b = dc - tmp[8 + j];
+        let c = mul2(tmp[4 + j]) - mul1(tmp[12 + j]);
+        let d = mul1(tmp[4 + j]) + mul2(tmp[12 + j]);
+        for (i, v) in [a + d, b + c, b - c, a - d].into_iter().enumerate() {
+            pixels[j * 4 + i] = (prediction(i, j) + (v >> 3)).clamp(0, 255) as u8;
+        }
+    }
+    pixels
+}
+
+/// The forward Walsh-Hadamard transform of the DC coefficients of the luma blocks.
+fn forward_wht(dc: &[i32; 16]) -> [i32; 16] {
+    let mut tmp = [0; 16];
+    for j in 0..4 {
+        let a0 = dc[j * 4] + dc[j * 4 + 2];
+        let a1 = dc[j * 4 + 1] + dc[j * 4 + 3];
+        let a2 = dc[j * 4 + 1] - dc[j * 4 + 3];
+        let a3 = dc[j * 4] - dc[j * 4 + 2];
+        tmp[j * 4] = a0 + a1;
+        tmp[1 + j * 4] = a3 + a2;
+        tmp[2 + j * 4] = a3 - a2;
+        tmp[3 + j * 4] = a0 - a1;
+    }
+    let mut out = [0; 16];
+    for i in 0..4 {
+        let a0 = tmp[i] + tmp[8 + i];
+        let a1 = tmp[4 + i] + tmp[12 + i];
+        let a2 = tmp[4 + i] - tmp[12 + i];
+        let a3 = tmp[i] - tmp[8 + i];
+        out[i] = (a0 + a1) >> 1;
+        out[4 + i] = (a3 + a2) >> 1;
+        out[8 + i] = (a3 - a2) >> 1;
+        out[12 + i] = (a0 - a1) >> 1;
+    }
+    out
+}
+
+fn inverse_wht(coefficients: &[i32; 16]) -> [i32; 16] {
+    let mut tmp = [0; 16];
+    for i in 0..4 {
+        let a0 = coefficients[i] + coefficients[12 + i];
+        let a1 = coefficients[4 + i] + coefficients[8 + i];
+        let a2 = coefficients[4 + i] - coefficients[8 + i];
+        let a3 = coefficients[i] - coefficients[12 + i];
+        tmp[i] = a0 + a1;
+        tmp[8 + i] = a0 - a1;
+        tmp[4 + i] = a3 + a2;
+        tmp[12 + i] = a3 - a2;
+    }
+    let mut dc = [0; 16];
+    for j in 0..4 {
+        let first = tmp[j * 4] + 3;
+        let a0 = first + tmp[3 + j * 4];
+        let a1 = tmp[1 + j * 4] + tmp[2 + j * 4];
+        let a2 = tmp[1 + j * 4] - tmp[2 + j * 4];
+        let a3 = first - tmp[3 + j * 4];
+        dc[j * 4] = (a0 + a1) >> 3;
+        dc[j * 4 + 1] = (a3 + a2) >> 3;
+        dc[j * 4 + 2] = (a0 - a1) >> 3;
+        dc[j * 4 + 3] = (a3 - a2) >> 3;
+    }
+    dc
+}
+
+fn write_frame_header(w: &mut BoolWriter, probas: &Probas, quantizer_index: usize) {
+    // The color space and the clamping type.
+    w.put_literal(0, 2);
+    // No segmentation.
+    w.put_literal(0, 1);
+    // A simple loop filter of level 0, without sharpness nor adjustments.
+    w.put_literal(0, 1);
+    w.put_literal(0, 6);
+    w.put_literal(0, 3);
+    w.put_literal(0, 1);
+    // A single token partition.
+    w.put_literal(0, 2);
+    w.put_literal(quantizer_index as u32, 7);
+    // No quantizer deltas.
+    w.put_literal(0, 5);
+    // Whether the probabilities are kept after the frame.
+    w.put_literal(0, 1);
+    for t in 0..4 {
+        for b in 0..8 {
+            for c in 0..3 {
+                for p in 0..11 {
+                    let update = probas[t][b][c][p] != COEFFS_PROBA0[t][b][c][p];
+                    w.put_bit(update, COEFFS_UPDATE_PROBA[t][b][c][p]);
+                    if update {
+                        w.put_literal(probas[t][b][c][p] as u32, 8);
+                    }
+                }
+            }
+        }
+    }
+    // No skipped macroblocks.
+    w.put_literal(0, 1);
+}
+
+fn write_modes(w: &mut BoolWriter, mb: &Macroblock) {
+    // A 16x16 luma prediction.
+    w.put_bit(true, 145);
+    let (first, second, second_proba) = match mb.luma_mode {
+        Mode::Dc => (false, false, 163),
+        Mode::Vertical => (false, true, 163),
+        Mode::Horizontal => (true, false, 128),
+        Mode::TrueMotion => (true, true, 128),
+    };
+    w.put_bit(first, 156);
+    w.put_bit(second, second_proba);
+    match mb.chroma_mode {
+        Mode::Dc => w.put_bit(false, 142),
+        Mode::Vertical => {
+            w.put_bit(true, 142);
+            w.put_bit(false, 114);
+        }
+        mode => {
+            w.put_bit(true, 142);
+            w.put_bit(true, 114);
+            w.put_bit(mode == Mode::TrueMotion, 183);
+        }
+    }
+}
+
+/// Receives the coefficient bits, to count or to write them.
+trait TokenSink {
+    /// A bit coded with the probability `[t][b][c][p]` of the coefficient probabilities.
+    fn put_coefficient_bit(&mut self, bit: bool, t: usize, b: usize, c: usize, p: usize);
+
+    /// A bit coded with a fixed probability.
+    fn put_bit(&mut self, bit: bool, proba: u8);
+}
+
+fn write_tokens(sink: &mut impl TokenSink, macroblocks: &[Macroblock], mb_width: usize) {
+    // The non-zero flags of the 4 luma, 2 U and 2 V block columns and of Y2, above and left.
+    let mut top = vec![[false; 9]; mb_width];
+    let mut left = [false; 9];
+    for (i, mb) in macroblocks.iter().enumerate() {
+        let top = &mut top[i % mb_width];
+        if i % mb_width == 0 {
+            left = [false; 9];
+        }
+        let context = top[8] as usize + left[8] as usize;
+        let nz = write_block(sink, TYPE_Y2, context, 0, &mb.y2);
+        top[8] = nz;
+        left[8] = nz;
+        for (n, block) in mb.y.iter().enumerate() {
+            let (x, y) = (n % 4, n / 4);
+            let context = top[x] as usize + left[y] as usize;
+            let nz = write_block(sink, TYPE_Y_AFTER_Y2, context, 1, block);
+            top[x] = nz;
+            left[y] = nz;
+        }
+        for (n, block) in mb.uv.iter().enumerate() {
+            let (x, y) = (4 + n / 4 * 2 + n % 2, 4 + n / 4 * 2 + n % 4 / 2);
+            let context = top[x] as usize + left[y] as usize;
+            let nz = write_block(sink, TYPE_UV, context, 0, block);
+            top[x] = nz;
+            left[y] = nz;
+        }
+    }
+}
+
+/// Writes the coefficients of a block from `first`, and returns whether any is non-zero.
+fn write_block(
+    sink: &mut impl TokenSink,
+    t: usize,
+    mut context: usize,
+    first: usize,
+    levels: &[i32; 16],
+) -> bool {
+    let last = match (first..16).rev().find(|&n| levels[ZIGZAG[n]] != 0) {
+        Some(last) => last,
+        None => {
+            sink.put_coefficient_bit(false, t, BANDS[first], context, 0);
+            return false;
+        }
+    };
+    let mut n = first;
+    while n < 16 {
+        let b = BANDS[n];
+        if n > last {
+            sink.put_coefficient_bit(false, t, b, context, 0);
+            break;
+        }
+        sink.put_coefficient_bit(true, t, b, context, 0);
+        // No end of block is coded after a zero.
+        while levels[ZIGZAG[n]] == 0 {
+            sink.put_coefficient_bit(false, t, BANDS[n], context, 1);
+            n += 1;
+            context = 0;
+        }
+        let b = BANDS[n];
+        sink.put_coefficient_bit(true, t, b, context, 1);
+        let level = levels[ZIGZAG[n]];
+        let value = level.unsigned_abs() as usize;
+        if value == 1 {
+            sink.put_coefficient_bit(false, t, b, context, 2);
+            context = 1;
+        } else {
+            sink.put_coefficient_bit(true, t, b, context, 2);
+            write_large_value(sink, t, b, context, value);
+            context = 2;
+        }
+        sink.put_bit(level < 0, 128);
+        n += 1;
+    }
+    true
+}
+
+fn write_large_value<S: TokenSink>(sink: &mut S, t: usize, b: usize, c: usize, value: usize) {
+    let put = |sink: &mut S, bit: bool, p: usize| sink.put_coefficient_bit(bit, t, b, c, p);
+    if value <= 4 {
+        put(sink, false, 3);
+        if value == 2 {
+            put(sink, false, 4);
+        } else {
+            put(sink, true, 4);
+            put(sink, value == 4, 5);
+        }
+    } else if value <= 10 {
+        put(sink, true, 3);
+        put(sink, false, 6);
+        if value <= 6 {
+            put(sink, false, 7);
+            sink.put_bit(value == 6, 159);
+        } else {
+            put(sink, true, 7);
+            sink.put_bit(value >= 9, 165);
+            sink.put_bit((value - 7) & 1 == 1, 145);
+        }
+    } else {
+        put(sink, true, 3);
+        put(sink, true, 6);
+        let category = match value {
+            11..=18 => 0,
+            19..=34 => 1,
+            35..=66 => 2,
+            _ => 3,
+        };
+        put(sink, category >= 2, 8);
+        put(sink, category & 1 == 1, 9 + category / 2);
+        let extra = value - (3 + (8 << category));
+        let probas = CAT_PROBAS[category];
+        for (i, &proba) in probas.iter().enumerate() {
+            sink.put_bit((extra >> (probas.len() - 1 - i)) & 1 == 1, proba);
+        }
+    }
+}
+
+/// Counts the zeros and the ones of each coefficient probability.
+struct Stats {
+    counts: [[[[[u32; 2]; 11]; 3]; 8]; 4],
+}
+
+impl TokenSink for Stats {
+    fn put_coefficient_bit(&mut self, bit: bool, t: usize, b: usize, c: usize, p: usize) {
+        self.counts[t][b][c][p][bit as usize] += 1;
+    }
+
+    fn put_bit(&mut self, _: bool, _: u8) {}
+}
+
+impl Stats {
+    /// The probabilities that code the counted bits with the fewest bits, when they save the
+    /// cost of their update.
+    fn probas(&self) -> Probas {
+        let mut probas = COEFFS_PROBA0;
+        for t in 0..4 {
+            for b in 0..8 {
+                for c in 0..3 {
+                    for p in 0..11 {
+                        let [zeros, ones] = self.counts[t][b][c][p];
+                        let total = zeros + ones;
+                        if total == 0 {
+                            continue;
+                        }
+                        let old = COEFFS_PROBA0[t][b][c][p];
+                        let new = (255 - (ones as u64 * 255 / total as u64) as u8).max(1);
+                        let update = COEFFS_UPDATE_PROBA[t][b][c][p];
+                        let old_cost = bit_cost(false, update) + cost(old, zeros, ones);
+                        let new_cost = bit_cost(true, update) + 8.0 + cost(new, zeros, ones);
+                        if new_cost < old_cost {
+                            probas[t][b][c][p] = new;
+                        }
+                    }
+                }
+            }
+        }
+        probas
+    }
+}
+
+fn bit_cost(bit: bool, proba: u8) -> f64 {
+    let proba = proba as f64 / 256.0;
+    -(if bit { 1.0 - proba } else { proba }).log2()
+}
+
+fn cost(proba: u8, zeros: u32, ones: u32) -> f64 {
+    zeros as f64 * bit_cost(false, proba) + ones as f64 * bit_cost(true, proba)
+}
+
+/// Writes the coefficient bits with the probabilities of the frame.
+struct TokenWriter<'a> {
+    writer: BoolWriter,
+    probas: &'a Probas,
+}
+
+impl TokenSink for TokenWriter<'_> {
+    fn put_coefficient_bit(&mut self, bit: bool, t: usize, b: usize, c: usize, p: usize) {
+        self.writer.put_bit(bit, self.probas[t][b][c][p]);
+    }
+
+    fn put_bit(&mut self, bit: bool, proba: u8) {
+        self.writer.put_bit(bit, proba);
+    }
+}
+
+/// The boolean entropy encoder of RFC 6386, as libwebp implements it.
+struct BoolWriter {
+    bytes: Vec<u8>,
+    /// The range minus one.
+    range: u32,
+    value: u32,
+    /// The number of 0xff bytes held until a carry is known.
+    run: usize,
+    bits: i32,
+}
+
+impl BoolWriter {
+    fn new() -> BoolWriter {
+        BoolWriter {
+            bytes: Vec::new(),
+            range: 254,
+            value: 0,
+            run: 0,
+            bits: -8,
+        }
+    }
+
+    fn put_bit(&mut self, bit: bool, proba: u8) {
+        let split = (self.range * proba as u32) >> 8;
+        if bit {
+            self.value += split + 1;
+            self.range -= split + 1;
+        } else {
+            self.range = split;
+        }
+        if self.range < 127 {
+            let shift = (self.range + 1).leading_zeros() - 24;
+            self.range = ((self.range + 1) << shift) - 1;
+            self.value <<= shift;
+            self.bits += shift as i32;
+            if self.bits > 0 {
+                self.flush();
+            }
+        }
+    }
+
+    /// Writes the bits of `value` from the most significant one, with a probability of a half.
+    fn put_literal(&mut self, value: u32, count: u32) {
+        for i in (0..count).rev() {
+            self.put_bit((value >> i) & 1 == 1, 128);
+        }
+    }
+
+    fn flush(&mut self) {
+        let shift = 8 + self.bits;
+        let bits = self.value >> shift;
+        self.value -= bits << shift;
+        self.bits -= 8;
+        if bits & 0xff != 0xff {
+            if bits & 0x100 != 0 {
+                if let Some(last) = self.bytes.last_mut() {
+                    *last += 1;
+                }
+            }
+            let pending = if bits & 0x100 != 0 { 0x00 } else { 0xff };
+            self.bytes.extend(std::iter::repeat(pending).take(self.run));
+            self.run = 0;
+            self.bytes.push(bits as u8);
+        } else {
+            self.run += 1;
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        self.put_literal(0, (9 - self.bits) as u32);
+        self.bits = 0;
+        self.flush();
+        self.bytes
+    }
+}
@@ -0,0 +1,78 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! A WebP encoder written in Rust, so that it builds for Wasm too.
+//!
+//! The lossless images are VP8L bitstreams. The lossy images are VP8 key frames, with the alpha
+//! channel compressed losslessly in an `ALPH` chunk.
+
+mod lossless;
+mod lossy;
+mod tables;
+
+/// The largest width and height of a WebP image.
+const MAX_DIMENSION: u32 = 16383;
+
+/// Encodes straight RGBA pixels to a WebP file. `quality` goes from 0 to 100 and is ignored by
+/// the lossless encoding.
+pub(crate) fn encode(
+    rgba: &[u8],
+    width: u32,
+    height: u32,
+    quality: f32,
+    lossless: bool,
+) -> Result<Vec<u8>, String> {
+    if width == 0 || height == 0 || width > MAX_DIMENSION || height > MAX_DIMENSION {
+        return Err(format!(
+            "the image size {width}x{height} is outside of the WebP limits (1 to {MAX_DIMENSION})"
+        ));
+    }
+    let (w, h) = (width as usize, height as usize);
+    let argb: Vec<u32> = rgba
+        .chunks_exact(4)
+        .map(|c| u32::from_be_bytes([c[3], c[0], c[1], c[2]]))
+        .collect();
+    let has_alpha = argb.iter().any(|pixel| pixel >> 24 != 0xff);
+
+    if lossless {
+        return Ok(riff(&[(
+            b"VP8L",
+            lossless::encode_image(&argb, w, h, has_alpha),
+        )]));
+    }
+    let vp8 = lossy::encode_image(&argb, w, h, quality);
+    if !has_alpha {
+        return Ok(riff(&[(b"VP8 ", vp8)]));
+    }
+    // The extended format, with the alpha flag.
+    let mut header = vec![0x10, 0, 0, 0];
+    header.extend_from_slice(&(width - 1).to_le_bytes()[..3]);
+    header.extend_from_slice(&(height - 1).to_le_bytes()[..3]);
+    Ok(riff(&[
+        (b"VP8X", header),
+        (b"ALPH", lossless::encode_alpha(&argb, w, h)),
+        (b"VP8 ", vp8),
+    ]))
+}
+
+/// Wraps the chunks in a RIFF container, the chunks are padded to an even size.
+fn riff(chunks: &[(&[u8; 4], Vec<u8>)]) -> Vec<u8> {
+    let size: usize = chunks
+        .iter()
+        .map(|(_, data)| 8 + data.len() + data.len() % 2)
+        .sum();
+    let mut file = Vec::with_capacity(8 + 4 + size);
+    file.extend_from_slice(b"RIFF");
+    file.extend_from_slice(&(4 + size as u32).to_le_bytes());
+    file.extend_from_slice(b"WEBP");
+    for (fourcc, data) in chunks {
+        file.extend_from_slice(fourcc.as_slice());
+        file.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        file.extend_from_slice(data);
+        if data.len() % 2 == 1 {
+            file.push(0);
+        }
+    }
+    file
+}
@@ -1,5 +1,5 @@
-import init, { Resvg as _Resvg, InitInput } from './wasm/dist'
-import { CustomFontsOptions, ResvgRenderOptions, SystemFontsOptions } from './wasm/index'
+import init, { Resvg as _Resvg, FontDatabase as _FontDatabase, InitInput } from './wasm/dist'
+import { CustomFontsOptions, FontFace, FontOptions, ResvgRenderOptions, SystemFontsOptions } from './wasm/index'
 
 let initialized = false
 
@@ -20,13 +20,18 @@ export const Resvg = class extends _Resvg {
   /**
    * @param {Uint8Array | string} svg
    * @param {ResvgRenderOptions | undefined} options
+   * @param {FontDatabase | undefined} fontDb Replaces the `font` options when given.
    */
-  constructor(svg: Uint8Array | string, options?: ResvgRenderOptions) {
+  constructor(svg: Uint8Array | string, options?: ResvgRenderOptions, fontDb?: _FontDatabase) {
     if (!initialized) throw new Error('Wasm has not been initialized. Call `initWasm()` function.')
 
     const font = options?.font
 
-    if (!!font && isCustomFontsOptions(font)) {
+    if (fontDb) {
+      // `fontDb` is borrowed rather than passed to the constructor, which would take its ownership.
+      const resvg = _Resvg.withFontDatabase(svg, { ...options, font: undefined }, fontDb)
+      return Object.setPrototypeOf(resvg, new.target.prototype)
+    } else if (!!font && isCustomFontsOptions(font)) {
       const serializableOptions = {
         ...options,
         font: {
@@ -35,13 +40,44 @@ export const Resvg = class extends _Resvg {
         },
       }
 
-      super(svg, JSON.stringify(serializableOptions), font.fontBuffers)
+      super(svg, serializableOptions, font.fontBuffers)
     } else {
-      super(svg, JSON.stringify(options))
+      super(svg, options)
     }
   }
 }
 
-function isCustomFontsOptions(value: SystemFontsOptions | CustomFontsOptions): value is CustomFontsOptions {
+export const FontDatabase = class extends _FontDatabase {
+  /**
+   * Fonts loaded once and shared by `Resvg` instances.
+   * @param {CustomFontsOptions | FontOptions | undefined} options
+   */
+  constructor(options?: CustomFontsOptions | FontOptions) {
+    if (!initialized) throw new Error('Wasm has not been initialized. Call `initWasm()` function.')
+
+    if (!!options && isCustomFontsOptions(options)) {
+      super({ ...options, fontBuffers: undefined }, options.fontBuffers)
+    } else {
+      super(options)
+    }
+  }
+}
+
+/**
+ * Lists the font faces loaded with the given font options.
+ * @param {CustomFontsOptions | FontOptions | undefined} options
+ */
+export const listFonts = (options?: CustomFontsOptions | FontOptions): FontFace[] => {
+  const fontDb = new FontDatabase(options)
+  try {
+    return fontDb.listFonts()
+  } finally {
+    fontDb.free()
+  }
+}
+
+function isCustomFontsOptions(
+  value: SystemFontsOptions | CustomFontsOptions | FontOptions,
+): value is CustomFontsOptions {
   return Object.prototype.hasOwnProperty.call(value, 'fontBuffers')
 }
@@ -15,13 +15,55 @@ declare class BBox {
 	*/
 	y: number;
 }
+/**
+* Fonts loaded once and shared by `Resvg` instances, loading system fonts is the most expensive
+* part of constructing a `Resvg`.
+*/
+declare class FontDatabase$1 {
+	free(): void;
+	/**
+	* @param {any} options
+	* @param {Array<any> | undefined} font_buffers
+	*/
+	constructor(options: any, font_buffers?: Array<any>);
+	/**
+	* Lists the loaded font faces
+	* @returns {any}
+	*/
+	listFonts(): any;
+}
 declare class RenderedImage {
 	free(): void;
 	/**
 	* Write the image data to Uint8Array
+	* @param {any} options
+	* @returns {Uint8Array}
+	*/
+	asPng(options: any): Uint8Array;
+	/**
+	* Encode the image data to WebP Uint8Array
+	* @param {any} options
+	* @returns {Uint8Array}
+	*/
+	asWebp(options: any): Uint8Array;
+	/**
+	* Encode the image data to JPEG Uint8Array
+	* @param {any} options
+	* @returns {Uint8Array}
+	*/
+	asJpeg(options: any): Uint8Array;
+	/**
+	* Encode the image data to AVIF Uint8Array
+	* @param {any} options
+	* @returns {Uint8Array}
+	*/
+	asAvif(options: any): Uint8Array;
+	/**
+	* Get the pixels of the image in the given format, optionally unpremultiplied
+	* @param {any} options
 	* @returns {Uint8Array}
 	*/
-	asPng(): Uint8Array;
+	getPixels(options: any): Uint8Array;
 	/**
 	* Get the PNG height
 	*/
@@ -59,6 +101,14 @@ export type ResvgRenderOptions = {
 	} | {
 		mode: "zoom";
 		value: number;
+	} | {
+		mode: "box";
+		value: {
+			width: number;
+			height: number;
+			fit?: "contain" | "cover" | "fill" | "inside" | "outside"; // Default: contain, letterboxed with the `background`.
+			align?: "xMinYMin" | "xMidYMin" | "xMaxYMin" | "xMinYMid" | "xMidYMid" | "xMaxYMid" | "xMinYMax" | "xMidYMax" | "xMaxYMax"; // Default: xMidYMid
+		};
 	};
 	background?: string; // Support CSS3 color, e.g. rgba(255, 255, 255, .8)
 	crop?: {
@@ -67,6 +117,15 @@ export type ResvgRenderOptions = {
 		right?: number;
 		bottom?: number;
 	};
+	/** Resource limits to render untrusted SVGs, each one is unlimited when unset. There is no parse timeout. */
+	limits?: {
+		maxPixels?: number; // The maximum width times height of the rendered image.
+		maxWidth?: number;
+		maxHeight?: number;
+		maxNodes?: number; // The maximum number of elements, counting each `<use>` element as the elements it references.
+		maxUseDepth?: number; // The maximum number of nested `<use>` references.
+		maxFilterRegion?: number; // The maximum number of pixels of a filter region.
+	};
 };
 export type FontOptions = {
 	defaultFontSize?: number; // Default: 12
@@ -76,9 +135,83 @@ export type FontOptions = {
 	cursiveFamily?: string;
 	fantasyFamily?: string;
 	monospaceFamily?: string;
+	fallbackFamilies?: string[] | {
+		[script: string]: string[];
+	}; // Default: [], families to take missing glyphs from in order, optionally keyed by Unicode script name (`Latin`, `Han`...) with a `default` list.
+};
+export type FontReportEntry = {
+	elementId: string; // The id of the `<text>` element.
+	text: string;
+	families: string[]; // The requested `font-family` list.
+	face: {
+		family: string;
+		postScriptName: string;
+		path: string | null; // Always `null` in Wasm.
+		bufferIndex: number | null; // The index in `fontBuffers`.
+		index: number; // The face index in a font collection.
+		weight: number;
+		style: "normal" | "italic" | "oblique";
+	} | null; // `null` when no font could be resolved.
+	missingCodePoints: number[]; // Code points no loaded font has a glyph for.
+};
+export type FontFace = {
+	families: {
+		name: string;
+		language: string;
+	}[]; // Every localized family name, the `English (United States)` ones first, e.g. `{ name: "Arial", language: "English (United States)" }`.
+	postScriptName: string;
+	weight: number;
+	style: "normal" | "italic" | "oblique";
+	stretch: string; // A CSS `font-stretch` keyword, e.g. "condensed".
+	monospaced: boolean;
+	path: string | null; // Always `null` in Wasm.
+	bufferIndex: number | null; // The index in `fontBuffers`.
+	index: number; // The face index in a font collection.
+};
+/** The errors thrown by resvg-js, `code` is stable and can be matched instead of the message. */
+export type ResvgError = Error & {
+	code:
+		| "ERR_SVG_PARSE"
+		| "ERR_SVG_INVALID_SIZE"
+		| "ERR_SVG_NOT_UTF8"
+		| "ERR_SVG_MALFORMED_GZIP"
+		| "ERR_SVG_ELEMENTS_LIMIT"
+		| "ERR_INVALID_VALUE"
+		| "ERR_INVALID_INPUT"
+		| "ERR_INVALID_OPTIONS"
+		| "ERR_ZERO_SIZED"
+		| "ERR_UNSUPPORTED_IMAGE"
+		| "ERR_NODE_NOT_FOUND"
+		| "ERR_INVALID_STRIDE"
+		| "ERR_TARGET_TOO_SMALL"
+		| "ERR_FONT_DECODING"
+		| "ERR_PNG_ENCODING"
+		| "ERR_WEBP_ENCODING"
+		| "ERR_JPEG_ENCODING"
+		| "ERR_JPEG_TOO_LARGE"
+		| "ERR_AVIF_ENCODING"
+		| "ERR_UTF8"
+		| "ERR_IO"
+		| "ERR_JSON"
+		| "ERR_LIMIT_PIXELS"
+		| "ERR_LIMIT_WIDTH"
+		| "ERR_LIMIT_HEIGHT"
+		| "ERR_LIMIT_NODES"
+		| "ERR_LIMIT_USE_DEPTH"
+		| "ERR_LIMIT_FILTER_REGION"
+		| "ERR_IMAGE_TOO_LARGE";
+	line?: number; // The line of an `ERR_SVG_PARSE` error, starting at 1.
+	column?: number; // The column of an `ERR_SVG_PARSE` error, starting at 1.
+	path?: string; // The path of the invalid value of an `ERR_INVALID_OPTIONS` error, e.g. `fitTo.mode`.
+	elementId?: string; // The id of an `ERR_NODE_NOT_FOUND` error.
+};
+export type ResvgWarning = {
+	level: "warn" | "error";
+	message: string;
+	elementId: string | null; // Best effort: the id is taken from the messages that name an element, e.g. `Rect 'r1' has an invalid 'width' value.`, and is `null` for the others.
 };
 export type CustomFontsOptions = {
-	fontBuffers: Uint8Array[]; // A list of raw font buffers to load.
+	fontBuffers: Uint8Array[]; // A list of raw font buffers to load, WOFF and WOFF2 are decoded.
 } & FontOptions;
 export type SystemFontsOptions = {
 	loadSystemFonts?: boolean; // Default: true. if set to false, it will be faster.
@@ -92,18 +225,44 @@ export type SystemFontsOptions = {
  */
 export declare const initWasm: (module_or_path: Promise<InitInput> | InitInput) => Promise<void>;
 export declare const Resvg: {
-	new (svg: Uint8Array | string, options?: ResvgRenderOptions): {
+	new (svg: Uint8Array | string, options?: ResvgRenderOptions, fontDb?: FontDatabase$1): {
 		free(): void;
 		render(): RenderedImage;
+		renderMany(variants: {
+			fitTo?: ResvgRenderOptions["fitTo"];
+			crop?: ResvgRenderOptions["crop"];
+			background?: string;
+		}[]): RenderedImage[];
+		renderNode(id: string, options?: {
+			fitTo?: ResvgRenderOptions["fitTo"];
+		}): RenderedImage;
+		renderInto(buffer: Uint8Array, options?: {
+			stride?: number;
+			x?: number;
+			y?: number;
+		}): void;
 		toString(): string;
+		toPdf(): Uint8Array;
 		innerBBox(): BBox | undefined;
 		getBBox(): BBox | undefined;
 		cropByBBox(bbox: BBox): void;
 		imagesToResolve(): any[];
 		resolveImage(href: string, buffer: Uint8Array): void;
+		fontReport(): FontReportEntry[];
 		readonly height: number;
+		readonly warnings: ResvgWarning[];
 		readonly width: number;
 	};
 };
+export declare const FontDatabase: {
+	new (options?: CustomFontsOptions | FontOptions): {
+		free(): void;
+		listFonts(): FontFace[];
+	};
+};
+/**
+ * Lists the font faces loaded with the given font options.
+ */
+export declare const listFonts: (options?: CustomFontsOptions | FontOptions) => FontFace[];
 
 export {};